@@ -1,22 +1,125 @@
-use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
-use std::process::exit;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
-use log::{error, info, warn};
-use reqwest::header;
-use tokio::process::Command;
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use xz2::read::XzDecoder;
 
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36"; // otherwise cloudflare throws a 1020 :(
-const LAUNCHER_URL: &str = "https://static.brickadia.com/launcher/1.4/brickadia-launcher.tar.xz";
-const LAUNCHER_FILE: &str = "launcher.tar.xz";
-pub const DATA_PATH: &str = "./data";
-pub const LAUNCHER_PATH: &str = "./data/brickadia-launcher";
+/// Where each version of the launcher is downloaded from, with `{}` standing for the version.
+const LAUNCHER_URL: &str = "https://static.brickadia.com/launcher/{}/brickadia-launcher.tar.xz";
+/// The start and end of the name of the archive being downloaded, inside the data folder. It's
+/// kept if the download fails, to be resumed by the next attempt.
+const ARCHIVE_PREFIX: &str = "launcher-";
+const ARCHIVE_SUFFIX: &str = ".tar.xz.part";
+/// The extension of the file next to a partial download holding the ETag or Last-Modified date
+/// of the archive it's part of.
+const VALIDATOR_EXTENSION: &str = "validator";
+/// The launcher's folder inside the data folder.
+const LAUNCHER_DIR: &str = "brickadia-launcher";
+/// The file inside the data folder that records which launcher is installed.
+const MARKER_FILE: &str = "launcher.toml";
+
+/// The version installed unless another is pinned.
+pub const DEFAULT_VERSION: &str = "1.4";
+
+/// The SHA-256 digests of the launcher versions known to the wrapper, as lowercase hex. Versions
+/// that aren't listed need a digest from the config, or unverified installs to be allowed.
+const KNOWN_DIGESTS: &[(&str, &str)] = &[];
 
 #[cfg(target_os = "windows")]
 pub const INSTALL_LOCATION: &str = "C:/Program Files/Brickadia";
 
-pub fn is_installed<'a>(_matches: &clap::ArgMatches<'a>) -> bool {
+/// Which launcher to install, and how to check the download.
+#[derive(Debug, Clone, Default)]
+pub struct Source {
+    /// The version to install, which is pinned if set. Otherwise any installed launcher is kept.
+    pub version: Option<String>,
+    /// Where to download the archive from, instead of the version's usual place.
+    pub url: Option<String>,
+    /// The archive's expected SHA-256 digest, instead of the version's known one.
+    pub sha256: Option<String>,
+    /// Whether to install an archive that has no digest to check against.
+    pub allow_unverified: bool,
+}
+
+impl Source {
+    pub fn version(&self) -> &str {
+        self.version.as_deref().unwrap_or(DEFAULT_VERSION)
+    }
+
+    pub fn url(&self) -> String {
+        match &self.url {
+            Some(url) => url.clone(),
+            None => LAUNCHER_URL.replace("{}", self.version()),
+        }
+    }
+
+    /// The digest the archive must have, if one is known.
+    pub fn sha256(&self) -> Option<String> {
+        match &self.sha256 {
+            Some(sha256) => Some(sha256.trim().to_lowercase()),
+            None => KNOWN_DIGESTS
+                .iter()
+                .find(|(version, _)| *version == self.version())
+                .map(|(_, sha256)| String::from(*sha256)),
+        }
+    }
+
+    /// Where the archive is downloaded to inside the data folder. It's named after the version
+    /// and the URL, so a partial download is only ever resumed from the same place.
+    fn archive_path(&self, data_dir: &Path) -> PathBuf {
+        let version: String = self
+            .version()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let url = format!("{:x}", Sha256::digest(self.url().as_bytes()));
+        data_dir.join(format!(
+            "{}{}-{}{}",
+            ARCHIVE_PREFIX,
+            version,
+            &url[..12],
+            ARCHIVE_SUFFIX
+        ))
+    }
+}
+
+/// What was installed, as recorded in the marker file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Installed {
+    pub version: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+/// Where the launcher is installed, given the data folder.
+pub fn launcher_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(LAUNCHER_DIR)
+}
+
+/// The launcher recorded as installed in the data folder, if any.
+pub fn installed(data_dir: &Path) -> Option<Installed> {
+    let contents = fs::read_to_string(data_dir.join(MARKER_FILE)).ok()?;
+    toml::from_str(&contents).ok()
+}
+
+/// Whether the launcher is installed, and is the pinned version if there is one.
+pub fn is_installed<'a>(
+    _matches: &clap::ArgMatches<'a>,
+    _data_dir: &Path,
+    _source: &Source,
+) -> bool {
     #[cfg(target_os = "windows")]
     return Path::new(
         _matches
@@ -26,82 +129,355 @@ pub fn is_installed<'a>(_matches: &clap::ArgMatches<'a>) -> bool {
     .exists();
 
     #[cfg(not(target_os = "windows"))]
-    return Path::new(LAUNCHER_PATH).exists();
+    return launcher_path(_data_dir).exists()
+        && _source.version.as_ref().is_none_or(|version| {
+            installed(_data_dir).is_some_and(|installed| &installed.version == version)
+        });
 }
 
 #[cfg(target_os = "windows")]
-pub async fn install<'a>(_matches: &clap::ArgMatches<'a>) {
+pub async fn install<'a>(
+    _matches: &clap::ArgMatches<'a>,
+    _data_dir: &Path,
+    _source: &Source,
+) -> Result<()> {
     // for windows installations, we can't programatically install the launcher, but we
     // can expect the user to already have it installed
     let install_location = _matches
         .value_of("install-location")
         .unwrap_or(INSTALL_LOCATION);
     if !Path::new(install_location).exists() {
-        error!("Brickadia is not installed! Please install it from https://brickadia.com/download");
-        exit(1);
+        bail!("Brickadia is not installed! Please install it from https://brickadia.com/download");
+    }
+    Ok(())
+}
+
+/// Downloads the launcher and installs it into the data folder, once the archive is verified.
+/// An archive with no digest to check against, whether built in or configured, is refused unless
+/// the source allows unverified installs. It's kept, so setting the digest doesn't download it
+/// again. A launcher that's already installed is only replaced once the new one is fully extracted, so a
+/// failed install leaves it as it was.
+#[cfg(not(target_os = "windows"))]
+pub async fn install<'a>(
+    _matches: &clap::ArgMatches<'a>,
+    data_dir: &Path,
+    source: &Source,
+) -> Result<()> {
+    fs::create_dir_all(data_dir)?;
+    let url = source.url();
+    let archive = source.archive_path(data_dir);
+    remove_other_downloads(data_dir, &archive);
+    info!("Downloading launcher {} from {}", source.version(), url);
+    download(&url, &archive).await?;
+
+    // the archive is checked before anything is extracted or replaced
+    let sha256 = sha256_file(&archive)?;
+    let launcher_path = launcher_path(data_dir);
+    match source.sha256() {
+        Some(expected) if expected != sha256 => {
+            // a bad archive isn't worth resuming
+            discard(&archive);
+            bail!(
+                "the launcher archive's SHA-256 is {}, but {} was expected, so it wasn't installed",
+                sha256,
+                expected
+            );
+        }
+        Some(_) => info!("Verified the launcher archive ({})", sha256),
+        None if source.allow_unverified => {
+            warn!(
+                "There's no known SHA-256 for launcher {}, installing it unverified ({})",
+                source.version(),
+                sha256
+            );
+        }
+        None => bail!(
+            "there's no known SHA-256 for launcher {}, so it wasn't installed. Set \
+             launcher.sha256 to {} if this archive is the right one, or set \
+             launcher.allow_unverified to install it anyway",
+            source.version(),
+            sha256
+        ),
     }
+
+    info!("Downloaded launcher, extracting");
+    let temp_dir = data_dir.join(format!(".{}.tmp", LAUNCHER_DIR));
+    let extracted = {
+        let archive = archive.clone();
+        let temp_dir = temp_dir.clone();
+        tokio::task::spawn_blocking(move || extract(&archive, &temp_dir)).await?
+    };
+    let installed = extracted.and_then(|()| swap(&temp_dir.join(LAUNCHER_DIR), &launcher_path));
+    let _ = fs::remove_dir_all(&temp_dir);
+    if fs::remove_file(&archive).is_err() {
+        warn!("Failed to clean up launcher file");
+    }
+    let _ = fs::remove_file(archive.with_extension(VALIDATOR_EXTENSION));
+    installed?;
+
+    let installed = Installed {
+        version: source.version().into(),
+        url,
+        sha256,
+    };
+    fs::write(data_dir.join(MARKER_FILE), toml::to_string(&installed)?)?;
+
+    info!("Launcher {} installed successfully!", installed.version);
+    Ok(())
 }
 
+/// Downloads `url` to `path`, showing its progress. A partial download left at `path` by an
+/// earlier attempt is resumed if the server supports it and the archive hasn't changed since,
+/// which the server checks against the ETag or Last-Modified date it sent the first time.
 #[cfg(not(target_os = "windows"))]
-pub async fn install<'a>(_matches: &clap::ArgMatches<'a>) {
-    info!("Downloading launcher");
+async fn download(url: &str, path: &Path) -> Result<()> {
+    let validator_path = path.with_extension(VALIDATOR_EXTENSION);
+    let validator = fs::read_to_string(&validator_path).unwrap_or_default();
+    // without a validator there's no telling whether the partial download is of the same archive
+    let resume_from = if validator.is_empty() {
+        0
+    } else {
+        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    };
 
-    // at this point, we assume we can use the .tar.xz archive from the website
     let client = reqwest::Client::new();
-    let response = client
-        .get(LAUNCHER_URL)
-        .header(header::USER_AGENT, USER_AGENT)
+    let mut request = client.get(url).header(header::USER_AGENT, USER_AGENT);
+    if resume_from > 0 {
+        request = request
+            .header(header::RANGE, format!("bytes={}-", resume_from))
+            .header(header::IF_RANGE, validator.as_str());
+    }
+    let mut response = request
         .send()
         .await
-        .expect("Failed to download the launcher!");
+        .map_err(|e| anyhow!("failed to download the launcher: {}", e))?;
 
-    if !response.status().is_success() {
-        error!("Failed to download the launcher!");
-        exit(1);
+    let status = response.status();
+    let content_range = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.strip_prefix("bytes "))
+        .map(String::from);
+    let (mut file, downloaded) = if status == StatusCode::PARTIAL_CONTENT {
+        let start = content_range
+            .as_deref()
+            .and_then(|range| range.split('-').next())
+            .and_then(|start| start.parse::<u64>().ok());
+        if resume_from == 0 || start != Some(resume_from) {
+            discard(path);
+            bail!(
+                "the server sent the wrong part of the launcher archive, run the install again \
+                 to download it from the start"
+            );
+        }
+        info!("Resuming the download from {} bytes", resume_from);
+        (OpenOptions::new().append(true).open(path)?, resume_from)
+    } else if status == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+        let total = content_range
+            .as_deref()
+            .and_then(|range| range.strip_prefix("*/"))
+            .and_then(|total| total.parse::<u64>().ok());
+        if total != Some(resume_from) {
+            discard(path);
+            bail!(
+                "the launcher archive changed since the last attempt, run the install again to \
+                 download it from the start"
+            );
+        }
+        // the earlier attempt got everything, which the digest check will confirm
+        return Ok(());
+    } else if status.is_success() {
+        // the whole archive, which replaces a partial download of one that has since changed
+        if resume_from > 0 {
+            info!("The launcher archive changed since the last attempt, downloading it again");
+        }
+        match response_validator(&response) {
+            Some(validator) => fs::write(&validator_path, validator)?,
+            None => {
+                let _ = fs::remove_file(&validator_path);
+            }
+        }
+        (File::create(path)?, 0)
+    } else {
+        bail!("failed to download the launcher ({})", status);
+    };
+
+    let total = response.content_length().map(|len| len + downloaded);
+    let mut progress = Progress::new(downloaded, total);
+    // whatever arrives is kept, so a dropped connection can be resumed
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| anyhow!("failed to download the launcher: {}", e))?
+    {
+        file.write_all(&chunk)?;
+        progress.advance(chunk.len() as u64);
     }
+    file.flush()?;
+    progress.finish();
 
-    let mut file = File::create("launcher.tar.xz").expect("Failed to create launcher file");
-    let bytes = response.bytes().await.unwrap();
-    file.write_all(&bytes[..])
-        .expect("Failed to write to launcher file");
+    if let Some(total) = total {
+        if progress.done != total {
+            bail!(
+                "the launcher download was cut short ({} of {} bytes), run the install again to resume it",
+                progress.done,
+                total
+            );
+        }
+    }
+    Ok(())
+}
 
-    info!("Downloaded launcher, extracting");
+/// What identifies the version of the archive in a response, for resuming it with `If-Range`:
+/// its ETag unless that's weak, which `If-Range` doesn't accept, or else its Last-Modified date.
+#[cfg(not(target_os = "windows"))]
+fn response_validator(response: &reqwest::Response) -> Option<String> {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &header::HeaderValue| value.to_str().ok())
+    };
+    header(header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(header::LAST_MODIFIED))
+        .map(String::from)
+}
 
-    if Path::new(LAUNCHER_PATH).exists() {
-        // remove existing launcher dir
-        fs::remove_dir_all(LAUNCHER_PATH).unwrap();
-    } else if !Path::new(DATA_PATH).exists() {
-        fs::create_dir_all(DATA_PATH).unwrap();
-    }
-
-    let extract = Command::new("tar")
-        .arg("xJf")
-        .arg(LAUNCHER_FILE)
-        .arg("-C")
-        .arg(DATA_PATH)
-        .output()
-        .await;
-
-    let extract_out = match extract {
-        Ok(x) => x,
-        Err(_) => {
-            error!("Failed to run extract command (is tar installed?)");
-            exit(1);
-        }
+/// Removes a partial download and its validator, so the next attempt starts from scratch.
+#[cfg(not(target_os = "windows"))]
+fn discard(path: &Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(path.with_extension(VALIDATOR_EXTENSION));
+}
+
+/// Removes partial downloads of other versions or from other places, which would never be resumed.
+#[cfg(not(target_os = "windows"))]
+fn remove_other_downloads(data_dir: &Path, archive: &Path) {
+    let entries = match fs::read_dir(data_dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
     };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_SUFFIX) && path != archive {
+            discard(&path);
+        }
+    }
+}
 
-    if !extract_out.status.success() {
-        error!("Failed to extract launcher");
-        exit(1);
+/// The SHA-256 digest of a file, as lowercase hex.
+#[cfg(not(target_os = "windows"))]
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Unpacks a .tar.xz archive into a fresh `dir`.
+#[cfg(not(target_os = "windows"))]
+fn extract(archive: &Path, dir: &Path) -> Result<()> {
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
     }
+    fs::create_dir_all(dir)?;
+
+    let decoder = XzDecoder::new(BufReader::new(File::open(archive)?));
+    tar::Archive::new(decoder)
+        .unpack(dir)
+        .map_err(|e| anyhow!("failed to extract the launcher: {}", e))?;
+    if !dir.join(LAUNCHER_DIR).is_dir() {
+        bail!("the launcher archive has no {} folder", LAUNCHER_DIR);
+    }
+    Ok(())
+}
 
-    // clean up launcher archive
-    match fs::remove_file(LAUNCHER_FILE) {
-        Ok(_) => (),
-        Err(_) => {
-            warn!("Failed to clean up launcher file")
+/// Moves the launcher at `new` to `path`, putting the old one back if that fails.
+#[cfg(not(target_os = "windows"))]
+fn swap(new: &Path, path: &Path) -> Result<()> {
+    let old = path.with_extension("old");
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+
+    let replacing = path.exists();
+    if replacing {
+        fs::rename(path, &old)?;
+    }
+    if let Err(e) = fs::rename(new, path) {
+        if replacing {
+            let _ = fs::rename(&old, path);
         }
+        bail!("failed to move the new launcher into place: {}", e);
+    }
+    if replacing && fs::remove_dir_all(&old).is_err() {
+        warn!("Failed to clean up the old launcher at {}", old.display());
     }
+    Ok(())
+}
 
-    info!("Launcher installed successfully!");
+/// A download's progress, drawn as a bar on a terminal and logged every quarter otherwise.
+#[cfg(not(target_os = "windows"))]
+struct Progress {
+    done: u64,
+    total: Option<u64>,
+    terminal: bool,
+    /// The last percentage drawn or logged.
+    shown: Option<u64>,
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Progress {
+    fn new(done: u64, total: Option<u64>) -> Self {
+        Progress {
+            done,
+            total,
+            terminal: io::stderr().is_terminal(),
+            shown: None,
+        }
+    }
+
+    fn advance(&mut self, len: u64) {
+        self.done += len;
+        let total = match self.total {
+            Some(total) if total > 0 => total,
+            _ => return,
+        };
+
+        let percent = (self.done * 100 / total).min(100);
+        let step = if self.terminal { 1 } else { 25 };
+        if self.shown.is_some_and(|shown| percent < shown + step) {
+            return;
+        }
+        self.shown = Some(percent);
+
+        let mb = |bytes: u64| bytes as f64 / 1_000_000.0;
+        if self.terminal {
+            let filled = (percent / 5) as usize;
+            eprint!(
+                "\r[{}{}] {:>3}% {:.1}/{:.1} MB",
+                "=".repeat(filled),
+                " ".repeat(20 - filled),
+                percent,
+                mb(self.done),
+                mb(total)
+            );
+        } else {
+            info!(
+                "Downloaded {}% ({:.1}/{:.1} MB)",
+                percent,
+                mb(self.done),
+                mb(total)
+            );
+        }
+    }
+
+    fn finish(&self) {
+        if self.terminal && self.shown.is_some() {
+            eprintln!();
+        }
+    }
 }
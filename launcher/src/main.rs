@@ -15,11 +15,8 @@ async fn main() {
     Dispatch::new()
         .format(move |out, message, record| {
             out.finish(format_args!(
-                "{} {}",
-                format!(
-                    "\x1B[{}m>>\x1B[0m",
-                    colors.get_color(&record.level()).to_fg_str()
-                ),
+                "\x1B[{}m>>\x1B[0m {}",
+                colors.get_color(&record.level()).to_fg_str(),
                 message
             ))
         })
@@ -31,7 +28,19 @@ async fn main() {
     let matches = clap::App::new("brust launcher installer")
         .about("Installs/looks for the Brickadia launcher")
         .author("voximity")
+        .arg(
+            clap::Arg::with_name("allow-unverified")
+                .long("allow-unverified")
+                .help("Install the launcher even if there's no known SHA-256 to check it against"),
+        )
         .get_matches();
 
-    launcher::install(&matches).await;
+    let source = launcher::Source {
+        allow_unverified: matches.is_present("allow-unverified"),
+        ..Default::default()
+    };
+    if let Err(e) = launcher::install(&matches, std::path::Path::new("data"), &source).await {
+        log::error!("Failed to install the launcher: {}", e);
+        std::process::exit(1);
+    }
 }
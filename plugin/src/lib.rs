@@ -1,15 +1,35 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufRead},
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
 use logging::PluginLogger;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use tokio::{
-    io::{self, AsyncBufReadExt, BufReader},
-    sync::mpsc::{self, UnboundedReceiver},
+use payloads::{
+    BanPayload, KickPayload, PluginSendPayload, ReloadPayload, ScheduleCreatePayload,
+    ScheduleNamePayload, StoreKeyPayload, StoreListPayload, StoreSetPayload, UnbanPayload,
+    WhisperPayload,
+};
+use rpc::RpcError;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use text::{Text, MAX_MESSAGE_LENGTH};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver},
+    oneshot,
 };
 
 pub mod logging;
 pub mod payloads;
 pub mod player;
 pub mod rpc;
+pub mod text;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Plugin {
@@ -18,9 +38,112 @@ pub struct Plugin {
     description: String,
     #[serde(default = "Plugin::default_target")]
     target: String,
+    /// The program to run instead of the target, such as `node` or `python3`. Programs without a
+    /// path are looked up on the `PATH`, others are relative to the plugin's folder.
+    #[serde(default)]
+    command: Option<String>,
+    /// Arguments passed to the plugin's command or target.
+    #[serde(default)]
+    args: Vec<String>,
+    /// Environment variables set for the plugin, on top of the wrapper's own.
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// The folder the plugin runs in, relative to the plugin's folder, which is the default.
+    #[serde(default)]
+    cwd: Option<String>,
+    /// A shell command, run in the plugin's folder, that builds its target.
+    #[serde(default)]
+    build: Option<String>,
+    /// The files and folders, relative to the plugin's folder, that the target is built from. If
+    /// left out, it's everything in the folder except build output and dependencies.
+    #[serde(default)]
+    sources: Vec<String>,
+    /// When to restart the plugin, which the server's config decides if it's left out.
+    #[serde(default)]
+    restart: Option<RestartPolicy>,
+    /// What the plugin needs to be allowed to do. The operator has to approve these.
+    #[serde(default)]
+    capabilities: Vec<Capability>,
+    /// The ids of plugins that have to be running before this one starts.
+    #[serde(default)]
+    depends: Vec<String>,
+    /// The ids of plugins that start before this one if they're installed.
+    #[serde(default)]
+    optional_depends: Vec<String>,
+}
+
+/// Something a plugin can be allowed to do, beyond logging and reading its config.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Write raw lines to the game's console (`writeln`), and reload plugins.
+    Console,
+    /// Message players (`broadcast`, `whisper`).
+    Broadcast,
+    /// Kick, ban and unban players.
+    Moderation,
+    /// Use the plugin's own store.
+    Storage,
+    /// Send messages to other plugins (`plugins.send`).
+    Messaging,
+}
+
+impl Capability {
+    pub const ALL: &'static [Capability] = &[
+        Capability::Console,
+        Capability::Broadcast,
+        Capability::Moderation,
+        Capability::Storage,
+        Capability::Messaging,
+    ];
+
+    /// The capability as written in plugin.toml.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Console => "console",
+            Capability::Broadcast => "broadcast",
+            Capability::Moderation => "moderation",
+            Capability::Storage => "storage",
+            Capability::Messaging => "messaging",
+        }
+    }
+
+    /// The capability a method sent by a plugin needs, or None if any plugin may use it.
+    pub fn required_for(method: &str) -> Option<Capability> {
+        match method {
+            "writeln" | "plugins.reload" => Some(Capability::Console),
+            "broadcast" | "whisper" => Some(Capability::Broadcast),
+            "players.kick" | "players.ban" | "players.unban" => Some(Capability::Moderation),
+            "plugins.send" => Some(Capability::Messaging),
+            _ if method.starts_with("store.") => Some(Capability::Storage),
+            _ => None,
+        }
+    }
+}
+
+/// When the server should restart a plugin whose process exited by itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Restart the plugin however it exited.
+    Always,
+    /// Restart the plugin only if it exited with an error or was killed.
+    #[default]
+    OnFailure,
+    /// Leave the plugin stopped.
+    Never,
 }
 
 static PLUGIN_LOGGER: PluginLogger = PluginLogger;
+static NEXT_REQUEST_ID: AtomicI32 = AtomicI32::new(0);
+/// Set once the listener has stopped, after which no response can arrive.
+static LISTENER_CLOSED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// Requests sent to the server that are still waiting on a response.
+    static ref PENDING_REQUESTS: Mutex<HashMap<rpc::Id, oneshot::Sender<rpc::Message>>> =
+        Mutex::new(HashMap::new());
+}
 
 impl Plugin {
     // static methods
@@ -36,29 +159,81 @@ impl Plugin {
     pub fn spawn_listener() -> UnboundedReceiver<rpc::Message> {
         let (sender, receiver) = mpsc::unbounded_channel::<rpc::Message>();
 
-        tokio::spawn(async move {
-            let reader = BufReader::new(io::stdin());
-            let mut lines = reader.lines();
+        // a plain thread rather than tokio's stdin, which would keep the runtime from shutting
+        // down when the plugin returns from main while it is still waiting on a line
+        thread::spawn(move || {
+            let stdin = io::stdin();
 
-            while let Some(line) = lines.next_line().await.unwrap() {
+            for line in stdin.lock().lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
                 let rpc_message: rpc::Message = match serde_json::from_str(line.as_str()) {
                     Ok(m) => m,
                     Err(_) => continue,
                 };
 
-                sender.send(rpc_message).unwrap();
+                // responses go to whoever is waiting on them rather than the receiver
+                if let rpc::Message::Response { id, .. } = &rpc_message {
+                    if let Some(waiting) = PENDING_REQUESTS.lock().unwrap().remove(id) {
+                        let _ = waiting.send(rpc_message);
+                    }
+                    continue;
+                }
+
+                if sender.send(rpc_message).is_err() {
+                    break;
+                }
             }
+
+            // dropping the senders fails the requests still waiting on a response
+            let mut pending = PENDING_REQUESTS.lock().unwrap();
+            LISTENER_CLOSED.store(true, Ordering::SeqCst);
+            pending.clear();
         });
 
         receiver
     }
 
+    /// Sends a request to the server and waits for its response.
+    /// The listener from `spawn_listener` must be running to receive it, and the request fails if
+    /// it stops first.
+    pub async fn request(method: &str, params: Option<Value>) -> Result<Value, RpcError> {
+        let id = rpc::Id::Int(NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst));
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending = PENDING_REQUESTS.lock().unwrap();
+            if LISTENER_CLOSED.load(Ordering::SeqCst) {
+                return Err(RpcError::new(
+                    RpcError::INTERNAL_ERROR,
+                    "no response from the server",
+                ));
+            }
+            pending.insert(id.clone(), sender);
+        }
+
+        Self::send(&rpc::Message::request(id, method, params));
+
+        match receiver.await {
+            Ok(rpc::Message::Response {
+                error: Some(error), ..
+            }) => Err(error),
+            Ok(rpc::Message::Response { result, .. }) => Ok(result.unwrap_or(Value::Null)),
+            _ => Err(RpcError::new(
+                RpcError::INTERNAL_ERROR,
+                "no response from the server",
+            )),
+        }
+    }
+
     fn default_target() -> String {
         "plugin".into()
     }
 
     // abstraction stuff
 
+    /// Broadcasts markup to the chat as-is. Use `broadcast_text` for anything containing untrusted input.
     pub fn broadcast(content: &str) {
         Self::send(&rpc::Message::notification(
             "broadcast",
@@ -66,6 +241,172 @@ impl Plugin {
         ));
     }
 
+    /// Broadcasts rich text to the chat, split over several messages if it is too long.
+    pub fn broadcast_text(text: &Text) {
+        for line in text.render_lines(MAX_MESSAGE_LENGTH) {
+            Self::broadcast(&line);
+        }
+    }
+
+    /// Privately messages a player by name or UUID. Fails if the player isn't online.
+    /// Like `broadcast`, the content is sent as-is.
+    pub async fn whisper(target: &str, content: &str) -> Result<(), RpcError> {
+        let payload = WhisperPayload {
+            target: target.into(),
+            content: content.into(),
+        };
+        Self::request("whisper", Some(serde_json::to_value(payload).unwrap()))
+            .await
+            .map(|_| ())
+    }
+
+    /// Privately messages rich text to a player, split over several messages if it is too long.
+    pub async fn whisper_text(target: &str, text: &Text) -> Result<(), RpcError> {
+        for line in text.render_lines(MAX_MESSAGE_LENGTH) {
+            Self::whisper(target, &line).await?;
+        }
+        Ok(())
+    }
+
+    /// Kicks an online player by name or UUID.
+    pub async fn kick(target: &str, reason: &str) -> Result<(), RpcError> {
+        let payload = KickPayload {
+            target: target.into(),
+            reason: reason.into(),
+        };
+        Self::request("players.kick", Some(serde_json::to_value(payload).unwrap()))
+            .await
+            .map(|_| ())
+    }
+
+    /// Bans a player by name or UUID, kicking them if they're online. Offline players can only
+    /// be banned by UUID. A duration of None bans them permanently.
+    pub async fn ban(
+        target: &str,
+        reason: &str,
+        duration: Option<Duration>,
+    ) -> Result<(), RpcError> {
+        let payload = BanPayload {
+            target: target.into(),
+            reason: reason.into(),
+            duration: duration.map(|d| d.as_secs()),
+        };
+        Self::request("players.ban", Some(serde_json::to_value(payload).unwrap()))
+            .await
+            .map(|_| ())
+    }
+
+    /// Lifts a ban by name or UUID.
+    pub async fn unban(target: &str) -> Result<(), RpcError> {
+        let payload = UnbanPayload {
+            target: target.into(),
+        };
+        Self::request(
+            "players.unban",
+            Some(serde_json::to_value(payload).unwrap()),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Asks the server to reload a plugin by id, or every plugin if `id` is None. The reload
+    /// happens after the server responds, so a plugin can safely reload itself.
+    pub async fn reload(id: Option<&str>) -> Result<(), RpcError> {
+        let payload = ReloadPayload {
+            id: id.map(String::from),
+        };
+        Self::request(
+            "plugins.reload",
+            Some(serde_json::to_value(payload).unwrap()),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Gets the plugin's config, which is the defaults from its plugin.toml merged with the
+    /// operator's overrides. It is also sent in a `config` notification when the plugin starts.
+    /// `T` can be `serde_json::Value` or a struct with a field for each option.
+    pub async fn config<T: DeserializeOwned>() -> Result<T, RpcError> {
+        let value = Self::request("config.get", None).await?;
+        serde_json::from_value(value)
+            .map_err(|e| RpcError::new(RpcError::INTERNAL_ERROR, e.to_string()))
+    }
+
+    /// Gets a value from the plugin's store, or None if the key isn't set.
+    pub async fn store_get<T: DeserializeOwned>(key: &str) -> Result<Option<T>, RpcError> {
+        let payload = StoreKeyPayload { key: key.into() };
+        let value =
+            Self::request("store.get", Some(serde_json::to_value(payload).unwrap())).await?;
+        serde_json::from_value(value)
+            .map_err(|e| RpcError::new(RpcError::INTERNAL_ERROR, e.to_string()))
+    }
+
+    /// Sets a key in the plugin's store, deleting it if the value serializes to null. The store is
+    /// saved before this returns.
+    pub async fn store_set<T: Serialize>(key: &str, value: &T) -> Result<(), RpcError> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| RpcError::new(RpcError::INVALID_PARAMS, e.to_string()))?;
+        let payload = StoreSetPayload {
+            key: key.into(),
+            value,
+        };
+        Self::request("store.set", Some(serde_json::to_value(payload).unwrap()))
+            .await
+            .map(|_| ())
+    }
+
+    /// Deletes a key from the plugin's store, returning whether it was set.
+    pub async fn store_delete(key: &str) -> Result<bool, RpcError> {
+        let payload = StoreKeyPayload { key: key.into() };
+        let value =
+            Self::request("store.delete", Some(serde_json::to_value(payload).unwrap())).await?;
+        Ok(value.as_bool().unwrap_or(false))
+    }
+
+    /// Lists the keys in the plugin's store that start with `prefix`, in order.
+    pub async fn store_list(prefix: &str) -> Result<Vec<String>, RpcError> {
+        let payload = StoreListPayload {
+            prefix: prefix.into(),
+        };
+        let value =
+            Self::request("store.list", Some(serde_json::to_value(payload).unwrap())).await?;
+        serde_json::from_value(value)
+            .map_err(|e| RpcError::new(RpcError::INTERNAL_ERROR, e.to_string()))
+    }
+
+    /// Sends a message to another plugin by id, which gets it in a `plugins.message` notification.
+    pub async fn send_to(target: &str, message: Value) -> Result<(), RpcError> {
+        let payload = PluginSendPayload {
+            target: target.into(),
+            message,
+        };
+        Self::request("plugins.send", Some(serde_json::to_value(payload).unwrap()))
+            .await
+            .map(|_| ())
+    }
+
+    /// Creates a schedule, or replaces the one with the same name. The plugin gets a
+    /// `schedule.fired` notification with the schedule's name each time it fires.
+    pub async fn schedule(schedule: ScheduleCreatePayload) -> Result<(), RpcError> {
+        Self::request(
+            "schedule.create",
+            Some(serde_json::to_value(schedule).unwrap()),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Cancels a schedule by name, returning whether it existed.
+    pub async fn cancel_schedule(name: &str) -> Result<bool, RpcError> {
+        let payload = ScheduleNamePayload { name: name.into() };
+        let value = Self::request(
+            "schedule.cancel",
+            Some(serde_json::to_value(payload).unwrap()),
+        )
+        .await?;
+        Ok(value.as_bool().unwrap_or(false))
+    }
+
     pub fn writeln(line: &str) {
         Self::send(&rpc::Message::notification("writeln", Some(json!(line))));
     }
@@ -87,4 +428,44 @@ impl Plugin {
     pub fn target(&self) -> &str {
         &self.target[..]
     }
+
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args[..]
+    }
+
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
+    }
+
+    pub fn build(&self) -> Option<&str> {
+        self.build.as_deref()
+    }
+
+    pub fn sources(&self) -> &[String] {
+        &self.sources[..]
+    }
+
+    pub fn restart(&self) -> Option<RestartPolicy> {
+        self.restart
+    }
+
+    pub fn capabilities(&self) -> &[Capability] {
+        &self.capabilities[..]
+    }
+
+    pub fn depends(&self) -> &[String] {
+        &self.depends[..]
+    }
+
+    pub fn optional_depends(&self) -> &[String] {
+        &self.optional_depends[..]
+    }
 }
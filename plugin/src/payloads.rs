@@ -1,9 +1,12 @@
 use std::convert::TryFrom;
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{logging::LogSeverity, rpc};
+use crate::{
+    logging::LogSeverity,
+    rpc::{self, RpcError},
+};
 
 // Each of the payload types in this file should implement TryFrom<rpc::Message>, and rpc::Message should implement From<the payload type>.
 
@@ -18,6 +21,12 @@ pub enum RpcDeserializationError {
     JsonError(#[from] serde_json::Error),
 }
 
+impl From<RpcDeserializationError> for RpcError {
+    fn from(error: RpcDeserializationError) -> Self {
+        RpcError::new(RpcError::INVALID_PARAMS, error.to_string())
+    }
+}
+
 /// A payload for sending log messages to the server.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LogPayload {
@@ -27,7 +36,7 @@ pub struct LogPayload {
 
 impl From<LogPayload> for rpc::Message {
     fn from(payload: LogPayload) -> Self {
-        rpc::Message::notification("log".into(), Some(serde_json::to_value(payload).unwrap()))
+        rpc::Message::notification("log", Some(serde_json::to_value(payload).unwrap()))
     }
 }
 
@@ -53,7 +62,7 @@ pub struct ChatPayload {
 
 impl From<ChatPayload> for rpc::Message {
     fn from(payload: ChatPayload) -> Self {
-        rpc::Message::notification("chat".into(), Some(serde_json::to_value(payload).unwrap()))
+        rpc::Message::notification("chat", Some(serde_json::to_value(payload).unwrap()))
     }
 }
 
@@ -69,3 +78,265 @@ impl TryFrom<rpc::Message> for ChatPayload {
         }
     }
 }
+
+/// Deserializes the params of a request, or of a notification with the same method.
+fn request_params<T: DeserializeOwned>(value: rpc::Message) -> Result<T, RpcDeserializationError> {
+    match value {
+        rpc::Message::Request { params, .. } | rpc::Message::Notification { params, .. } => Ok(
+            serde_json::from_value(params.ok_or(RpcDeserializationError::NoValue)?)?,
+        ),
+        _ => Err(RpcDeserializationError::WrongRpcType),
+    }
+}
+
+/// A payload for privately messaging a player, by name or UUID.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WhisperPayload {
+    pub target: String,
+    pub content: String,
+}
+
+impl TryFrom<rpc::Message> for WhisperPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for kicking an online player, by name or UUID.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KickPayload {
+    pub target: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl TryFrom<rpc::Message> for KickPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for banning a player, by name or UUID. Offline players can only be banned by UUID.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BanPayload {
+    pub target: String,
+    #[serde(default)]
+    pub reason: String,
+    /// How long the ban lasts in seconds, or None for a permanent ban.
+    pub duration: Option<u64>,
+}
+
+impl TryFrom<rpc::Message> for BanPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for lifting a ban, by name or UUID.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnbanPayload {
+    pub target: String,
+}
+
+impl TryFrom<rpc::Message> for UnbanPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload telling plugins that another plugin started (`plugin.started`) or stopped (`plugin.stopped`).
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PluginStatusPayload {
+    /// The plugin's id, which is the name of its folder.
+    pub id: String,
+    pub name: String,
+    /// The stopped plugin's exit code, if it exited by itself with one.
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+}
+
+impl PluginStatusPayload {
+    /// Makes the notification for this payload, `plugin.started` or `plugin.stopped`.
+    pub fn notification(&self, method: &str) -> rpc::Message {
+        rpc::Message::notification(method, Some(serde_json::to_value(self).unwrap()))
+    }
+}
+
+impl TryFrom<rpc::Message> for PluginStatusPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for asking the server to reload a plugin by id, or every plugin if there's no id.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReloadPayload {
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+impl TryFrom<rpc::Message> for ReloadPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload naming a key in the plugin's store, for `store.get` and `store.delete`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StoreKeyPayload {
+    pub key: String,
+}
+
+impl TryFrom<rpc::Message> for StoreKeyPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for setting a key in the plugin's store to any JSON value. Setting it to null
+/// deletes the key.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StoreSetPayload {
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+impl TryFrom<rpc::Message> for StoreSetPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for listing the keys in the plugin's store that start with a prefix.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StoreListPayload {
+    #[serde(default)]
+    pub prefix: String,
+}
+
+impl TryFrom<rpc::Message> for StoreListPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for sending a message to another plugin, by id.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PluginSendPayload {
+    pub target: String,
+    pub message: serde_json::Value,
+}
+
+impl TryFrom<rpc::Message> for PluginSendPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload carrying a message from another plugin, sent as a `plugins.message` notification.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PluginMessagePayload {
+    /// The id of the plugin that sent the message.
+    pub from: String,
+    pub message: serde_json::Value,
+}
+
+impl From<PluginMessagePayload> for rpc::Message {
+    fn from(payload: PluginMessagePayload) -> Self {
+        rpc::Message::notification(
+            "plugins.message",
+            Some(serde_json::to_value(payload).unwrap()),
+        )
+    }
+}
+
+impl TryFrom<rpc::Message> for PluginMessagePayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload for `schedule.create`, which creates one of the plugin's schedules or replaces the one
+/// with the same name. A schedule fires on a `cron` expression, `every` so many seconds, or once
+/// after a `delay` in seconds. A delay given with `every` puts off the first firing.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ScheduleCreatePayload {
+    pub name: String,
+    /// A cron expression in local time, with five fields (minute to day of week) or six (seconds first).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay: Option<f64>,
+    /// Whether the schedule is kept when the plugin or the server restarts.
+    #[serde(default)]
+    pub persist: bool,
+}
+
+impl TryFrom<rpc::Message> for ScheduleCreatePayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload naming one of the plugin's schedules, for `schedule.cancel`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScheduleNamePayload {
+    pub name: String,
+}
+
+impl TryFrom<rpc::Message> for ScheduleNamePayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
+
+/// A payload telling a plugin one of its schedules fired, sent as a `schedule.fired` notification.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScheduleFiredPayload {
+    pub name: String,
+}
+
+impl From<ScheduleFiredPayload> for rpc::Message {
+    fn from(payload: ScheduleFiredPayload) -> Self {
+        rpc::Message::notification(
+            "schedule.fired",
+            Some(serde_json::to_value(payload).unwrap()),
+        )
+    }
+}
+
+impl TryFrom<rpc::Message> for ScheduleFiredPayload {
+    type Error = RpcDeserializationError;
+
+    fn try_from(value: rpc::Message) -> Result<Self, Self::Error> {
+        request_params(value)
+    }
+}
@@ -3,7 +3,7 @@ use std::{error::Error, fmt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(untagged)]
 pub enum Id {
     Str(String),
@@ -17,6 +17,41 @@ pub struct RpcError {
     data: Option<Value>,
 }
 
+impl RpcError {
+    /// The params of a request were missing or malformed.
+    pub const INVALID_PARAMS: i32 = -32602;
+    /// The method of a request isn't known.
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    /// Something went wrong handling a request.
+    pub const INTERNAL_ERROR: i32 = -32603;
+    /// The player a request targets isn't online.
+    pub const PLAYER_NOT_FOUND: i32 = -32000;
+    /// The player a request targets isn't banned.
+    pub const NOT_BANNED: i32 = -32001;
+    /// The plugin doesn't have the capability a method needs.
+    pub const PERMISSION_DENIED: i32 = -32002;
+
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message[..]
+    }
+
+    pub fn data(&self) -> Option<&Value> {
+        self.data.as_ref()
+    }
+}
+
 impl fmt::Display for RpcError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} (code {})", self.message, self.code)
@@ -25,17 +60,18 @@ impl fmt::Display for RpcError {
 
 impl Error for RpcError {}
 
+// requests must come before notifications, otherwise untagged deserialization drops their ids
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum Message {
-    Notification {
+    Request {
         jsonrpc: String,
+        id: Id,
         method: String,
         params: Option<Value>,
     },
-    Request {
+    Notification {
         jsonrpc: String,
-        id: Id,
         method: String,
         params: Option<Value>,
     },
@@ -74,6 +110,23 @@ impl Message {
         }
     }
 
+    /// A response to a request carrying either its result or its error.
+    pub fn reply(id: Id, result: Result<Value, RpcError>) -> Self {
+        match result {
+            Ok(value) => Self::response(id, Some(value), None),
+            Err(error) => Self::response(id, None, Some(error)),
+        }
+    }
+
+    /// Gets the id of the message. Some for Requests/Responses, None for Notifications.
+    pub fn id(&self) -> Option<&Id> {
+        match self {
+            Message::Notification { .. } => None,
+            Message::Request { id, .. } => Some(id),
+            Message::Response { id, .. } => Some(id),
+        }
+    }
+
     /// Gets an Option<&str> representing the method of the message. Some for Notifications/Requests, None for Responses.
     pub fn method(&self) -> Option<&str> {
         match self {
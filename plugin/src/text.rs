@@ -0,0 +1,329 @@
+//! Building Brickadia rich text for chat.
+//!
+//! Brickadia chat understands a small markup language (`<b>bold</>`, `<color="ff0000">red</>`,
+//! `<emoji>smile</>`, ...). Text pushed into a [`Text`] is escaped, so player names and messages
+//! can't break formatting or inject their own tags.
+//!
+//! ```
+//! use plugin::text::{Span, Text};
+//!
+//! let text = Text::new()
+//!     .push(Span::new("<Alice>").bold().color("ffaa00"))
+//!     .push(" joined the server ")
+//!     .push(Span::emoji("smile"));
+//!
+//! assert_eq!(
+//!     text.render(),
+//!     "<color=\"ffaa00\"><b>&lt;Alice&gt;</></> joined the server <emoji>smile</>"
+//! );
+//! ```
+
+/// The longest markup sent in a single chat message. Longer text is split over several messages.
+pub const MAX_MESSAGE_LENGTH: usize = 512;
+
+/// Escapes untrusted text so it is displayed literally in chat.
+///
+/// Line breaks and other control characters are replaced with spaces, since the game console
+/// would treat them as the end of a command.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            ';' => escaped.push_str("&scl;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The content of a span.
+#[derive(Debug, Clone)]
+enum Content {
+    /// Untrusted text, escaped when rendered.
+    Text(String),
+    /// An emoji by name.
+    Emoji(String),
+    /// Trusted markup, sent as-is.
+    Raw(String),
+}
+
+/// A piece of text sharing one style.
+#[derive(Debug, Clone)]
+pub struct Span {
+    content: Content,
+    color: Option<String>,
+    bold: bool,
+    italic: bool,
+    size: Option<u32>,
+    link: Option<String>,
+}
+
+impl Span {
+    /// A span of untrusted text.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self::with_content(Content::Text(text.into()))
+    }
+
+    /// An emoji, e.g. `smile`.
+    pub fn emoji(name: &str) -> Self {
+        Self::with_content(Content::Emoji(escape(name)))
+    }
+
+    /// Trusted markup that is sent without escaping. Raw spans are never split.
+    pub fn raw(markup: impl Into<String>) -> Self {
+        Self::with_content(Content::Raw(markup.into()))
+    }
+
+    fn with_content(content: Content) -> Self {
+        Span {
+            content,
+            color: None,
+            bold: false,
+            italic: false,
+            size: None,
+            link: None,
+        }
+    }
+
+    /// Colors the span with a hex color such as `ff0000`. Invalid colors are ignored.
+    pub fn color(mut self, hex: &str) -> Self {
+        let hex = hex.trim_start_matches('#');
+        if matches!(hex.len(), 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            self.color = Some(hex.into());
+        }
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    /// Sets the font size of the span.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Makes the span a link to `url`.
+    pub fn link(mut self, url: &str) -> Self {
+        self.link = Some(escape(url));
+        self
+    }
+
+    /// The markup opening and closing this span's style.
+    fn tags(&self) -> (String, String) {
+        let mut open = String::new();
+        let mut depth = 0;
+
+        if let Some(url) = &self.link {
+            open.push_str(&format!("<link=\"{}\">", url));
+            depth += 1;
+        }
+        if let Some(color) = &self.color {
+            open.push_str(&format!("<color=\"{}\">", color));
+            depth += 1;
+        }
+        if let Some(size) = self.size {
+            open.push_str(&format!("<size=\"{}\">", size));
+            depth += 1;
+        }
+        if self.bold {
+            open.push_str("<b>");
+            depth += 1;
+        }
+        if self.italic {
+            open.push_str("<i>");
+            depth += 1;
+        }
+
+        (open, "</>".repeat(depth))
+    }
+
+    /// Renders the span into pieces of markup no longer than `max` where possible.
+    fn render_pieces(&self, max: usize) -> Vec<String> {
+        let (open, close) = self.tags();
+        let body = match &self.content {
+            Content::Text(text) => text,
+            Content::Emoji(name) => return vec![format!("{}<emoji>{}</>{}", open, name, close)],
+            Content::Raw(markup) => return vec![format!("{}{}{}", open, markup, close)],
+        };
+
+        // split the text on character boundaries so escape sequences are never cut in half
+        let room = max.saturating_sub(open.len() + close.len()).max(1);
+        let mut pieces = vec![];
+        let mut piece = String::new();
+        for c in body.chars() {
+            let escaped = escape(c.encode_utf8(&mut [0; 4]));
+            if !piece.is_empty() && piece.len() + escaped.len() > room {
+                pieces.push(format!("{}{}{}", open, piece, close));
+                piece.clear();
+            }
+            piece.push_str(&escaped);
+        }
+        if !piece.is_empty() || pieces.is_empty() {
+            pieces.push(format!("{}{}{}", open, piece, close));
+        }
+
+        pieces
+    }
+}
+
+impl From<&str> for Span {
+    fn from(text: &str) -> Self {
+        Span::new(text)
+    }
+}
+
+impl From<String> for Span {
+    fn from(text: String) -> Self {
+        Span::new(text)
+    }
+}
+
+/// A chat message made of styled spans.
+#[derive(Debug, Clone, Default)]
+pub struct Text {
+    spans: Vec<Span>,
+}
+
+impl Text {
+    pub fn new() -> Self {
+        Text::default()
+    }
+
+    /// Appends a span, or plain untrusted text.
+    pub fn push(mut self, span: impl Into<Span>) -> Self {
+        self.spans.push(span.into());
+        self
+    }
+
+    /// Renders the whole message as one line of markup.
+    pub fn render(&self) -> String {
+        self.render_lines(usize::MAX).concat()
+    }
+
+    /// Renders the message as lines of markup, each at most `max` bytes long where possible.
+    /// Only raw spans, emoji and the tags of a single character can exceed it.
+    pub fn render_lines(&self, max: usize) -> Vec<String> {
+        let mut lines = vec![];
+        let mut line = String::new();
+
+        for span in self.spans.iter() {
+            for piece in span.render_pieces(max) {
+                if !line.is_empty() && line.len() + piece.len() > max {
+                    lines.push(line);
+                    line = String::new();
+                }
+                line.push_str(&piece);
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+
+        lines
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        Text::new().push(text)
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        Text::new().push(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup_and_command_separators() {
+        assert_eq!(escape("a;b"), "a&scl;b");
+        assert_eq!(escape("<b>&\"</>"), "&lt;b&gt;&amp;&quot;&lt;/&gt;");
+        assert_eq!(escape("C:\\path\\"), "C:\\\\path\\\\");
+        // an already escaped sequence is escaped again rather than passed through
+        assert_eq!(escape("&scl;"), "&amp;scl&scl;");
+    }
+
+    #[test]
+    fn replaces_control_characters_with_spaces() {
+        assert_eq!(escape("one\ntwo\r\nthree"), "one two  three");
+        assert_eq!(escape("\t\u{7}\u{0}\u{7f}"), "    ");
+        assert_eq!(escape("héllo wörld ✓"), "héllo wörld ✓");
+    }
+
+    #[test]
+    fn fills_lines_up_to_the_limit() {
+        let text = Text::from("a".repeat(MAX_MESSAGE_LENGTH));
+        assert_eq!(text.render_lines(MAX_MESSAGE_LENGTH).len(), 1);
+
+        let text = Text::from("a".repeat(MAX_MESSAGE_LENGTH + 1));
+        let lines = text.render_lines(MAX_MESSAGE_LENGTH);
+        assert_eq!(lines, vec!["a".repeat(MAX_MESSAGE_LENGTH), "a".into()]);
+    }
+
+    #[test]
+    fn splits_multibyte_characters_whole() {
+        // 2 and 3 byte characters, neither of which divides 512 evenly in the second case
+        for c in ['é', '€'] {
+            let body = c.to_string().repeat(400);
+            let lines = Text::from(body.as_str()).render_lines(MAX_MESSAGE_LENGTH);
+
+            assert!(lines.len() > 1);
+            assert!(lines.iter().all(|line| line.len() <= MAX_MESSAGE_LENGTH));
+            // every line but the last is as full as whole characters allow
+            let full = MAX_MESSAGE_LENGTH / c.len_utf8() * c.len_utf8();
+            assert!(lines[..lines.len() - 1]
+                .iter()
+                .all(|line| line.len() == full));
+            assert_eq!(lines.concat(), body);
+        }
+    }
+
+    #[test]
+    fn keeps_escape_sequences_whole() {
+        let body = ";".repeat(200);
+        let pieces = Span::new(body).render_pieces(MAX_MESSAGE_LENGTH);
+
+        // 102 escaped semicolons fit, the 103rd would cross the limit
+        assert_eq!(pieces[0], "&scl;".repeat(102));
+        assert_eq!(pieces[1], "&scl;".repeat(98));
+    }
+
+    #[test]
+    fn repeats_tags_around_each_piece() {
+        let pieces = Span::new("a".repeat(600))
+            .bold()
+            .render_pieces(MAX_MESSAGE_LENGTH);
+
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].len(), MAX_MESSAGE_LENGTH);
+        assert_eq!(pieces[0], format!("<b>{}</>", "a".repeat(506)));
+        assert_eq!(pieces[1], format!("<b>{}</>", "a".repeat(94)));
+    }
+
+    #[test]
+    fn starts_a_new_line_rather_than_splitting_a_piece() {
+        let text = Text::new().push("a".repeat(500)).push(Span::emoji("smile"));
+        let lines = text.render_lines(MAX_MESSAGE_LENGTH);
+
+        assert_eq!(lines, vec!["a".repeat(500), "<emoji>smile</>".into()]);
+    }
+}
@@ -1,7 +1,7 @@
 use std::{convert::TryInto, time::Duration};
 
 use log::{debug, error, info, warn};
-use plugin::{Plugin, payloads::ChatPayload, player::Player, rpc};
+use plugin::{Plugin, payloads::ChatPayload, player::Player, rpc, text::{Span, Text}};
 use tokio::time::sleep;
 
 #[tokio::main]
@@ -21,7 +21,9 @@ async fn main() {
                     _ => continue
                 };
 
-                Plugin::broadcast(format!("{} is connecting! Their UUID is {}", player.name, player.uuid).as_str());
+                Plugin::broadcast_text(&Text::new()
+                    .push(Span::new(player.name).bold())
+                    .push(format!(" is connecting! Their UUID is {}", player.uuid)));
             },
             Some("chat") => {
                 // a user chats
@@ -31,6 +33,25 @@ async fn main() {
                     let line = &payload.message[8..];
                     Plugin::writeln(line);
                 }
+
+                // replies only go to whoever asked, so they don't spam everyone
+                let reply = match payload.message.trim() {
+                    "ping" => Text::from("pong"),
+                    "pong" => Text::from("ping"),
+                    "!help" => Text::new()
+                        .push(Span::new("Ping Pong Plugin").bold())
+                        .push(": say ping or pong, and I'll answer"),
+                    _ => continue
+                };
+
+                if let Err(e) = Plugin::whisper_text(&payload.user, &reply).await {
+                    warn!("Couldn't reply to {}: {}", payload.user, e);
+                }
+            },
+            Some("shutdown") => {
+                // the server is stopping, so exit before it has to kill us
+                info!("Shutting down");
+                break;
             },
             _ => ()
         }
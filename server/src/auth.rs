@@ -0,0 +1,258 @@
+use std::{
+    env, fmt, fs,
+    io::{self, BufRead, ErrorKind, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, bail, Result};
+use dialoguer::{theme::ColorfulTheme, Input, Password};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+
+/// The environment variables the account's credentials can be given in.
+const EMAIL_VAR: &str = "BRIXIDE_EMAIL";
+const PASSWORD_VAR: &str = "BRIXIDE_PASSWORD";
+/// The credentials file read from the data folder unless the config names another.
+pub const CREDENTIALS_FILE: &str = "credentials.toml";
+/// The file handing the credentials to the game, inside the data folder. The game only reads
+/// command line files ending in `.txt`.
+const COMMAND_LINE_FILE: &str = "credentials.cmdline.txt";
+
+lazy_static! {
+    /// Matches the errors the game logs under its own login category. Other categories, like the
+    /// server list's checks of joining players, and chat are left alone.
+    static ref AUTH_FAILURE: Regex = Regex::new(r"^LogAuth: (Error|Fatal): ").unwrap();
+}
+
+/// The credentials taken out of the environment at startup.
+static ENV_CREDENTIALS: OnceLock<Option<(String, String)>> = OnceLock::new();
+
+/// Where the account's credentials came from, for error messages.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Environment,
+    File(PathBuf),
+    Stdin,
+    Prompt,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Environment => write!(f, "{} and {}", EMAIL_VAR, PASSWORD_VAR),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Stdin => write!(f, "stdin"),
+            Source::Prompt => write!(f, "the prompt"),
+        }
+    }
+}
+
+/// The account the game hosts the server with. It only needs them until it has logged in once.
+#[derive(Deserialize)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+    #[serde(skip, default = "Credentials::file_source")]
+    pub source: Source,
+}
+
+impl Credentials {
+    fn file_source() -> Source {
+        Source::File(PathBuf::new())
+    }
+}
+
+/// Whether the game has logged in before, so it doesn't need credentials.
+pub fn is_authenticated(data_dir: &Path) -> bool {
+    data_dir.join("Saved").join("Auth").exists()
+}
+
+/// Reads the credentials from the environment and removes them from it, so plugins and build
+/// commands started later don't inherit the password. Called first thing in `main`, before the
+/// runtime and its threads exist, as changing the environment isn't safe once there are others.
+pub fn take_env_credentials() {
+    let email = env::var(EMAIL_VAR);
+    let password = env::var(PASSWORD_VAR);
+    env::remove_var(EMAIL_VAR);
+    env::remove_var(PASSWORD_VAR);
+    let _ = ENV_CREDENTIALS.set(email.ok().zip(password.ok()));
+}
+
+/// Finds the account's credentials: from the environment, then the credentials file, then stdin
+/// if `from_stdin`, and last by prompting if the wrapper is running in a terminal.
+pub fn credentials(file: &Path, from_stdin: bool) -> Result<Credentials> {
+    if let Some(Some((email, password))) = ENV_CREDENTIALS.get() {
+        return Ok(Credentials {
+            email: email.clone(),
+            password: password.clone(),
+            source: Source::Environment,
+        });
+    }
+
+    match fs::read_to_string(file) {
+        Ok(contents) => {
+            check_private(file)?;
+            let mut credentials: Credentials = toml::from_str(&contents)
+                .map_err(|e| anyhow!("invalid credentials in {}: {}", file.display(), e))?;
+            credentials.source = Source::File(file.to_path_buf());
+            return Ok(credentials);
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => (),
+        Err(e) => bail!("failed to read {}: {}", file.display(), e),
+    }
+
+    if from_stdin {
+        // the email, then the password, each on its own line
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        let mut line = |what: &str| -> Result<String> {
+            match lines.next() {
+                Some(line) => Ok(line?.trim().to_owned()),
+                None => bail!("stdin closed before the account's {} was given", what),
+            }
+        };
+        let email = line("email")?;
+        let password = line("password")?;
+        return Ok(Credentials {
+            email,
+            password,
+            source: Source::Stdin,
+        });
+    }
+
+    if !io::stdin().is_terminal() {
+        bail!(
+            "the game needs account credentials to log in for the first time. Set {} and {}, \
+             write them to {} or pass --credentials-stdin",
+            EMAIL_VAR,
+            PASSWORD_VAR,
+            file.display()
+        );
+    }
+
+    log::info!(
+        "Please enter your account information to host the server (this is a one-time process)"
+    );
+    let email: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Account email")
+        .interact()?;
+    let password: String = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt("Account password")
+        .interact()?;
+
+    Ok(Credentials {
+        email,
+        password,
+        source: Source::Prompt,
+    })
+}
+
+/// Refuses credentials files other users can read.
+#[cfg(unix)]
+fn check_private(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        bail!(
+            "{} can be read by other users (its mode is {:o}), run chmod 600 on it",
+            path.display(),
+            mode & 0o777
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// A file that hands the credentials to the game through `-CmdLineFile`, so they don't show up in
+/// the process list like `-Password` would. Only the wrapper's user can read it, and it's removed
+/// once dropped.
+pub struct CommandLineFile {
+    path: PathBuf,
+    pub source: Source,
+}
+
+impl CommandLineFile {
+    pub fn write(data_dir: &Path, credentials: Credentials) -> Result<Self> {
+        // the game can't read quotes inside quoted values
+        if credentials.email.contains('"') || credentials.password.contains('"') {
+            bail!("credentials containing \" can't be passed to the game");
+        }
+
+        fs::create_dir_all(data_dir)?;
+        let path = env::current_dir()?.join(data_dir).join(COMMAND_LINE_FILE);
+        // a file left over from a crash keeps its permissions if it's reused, so it's replaced
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => (),
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(&path)?;
+        write!(
+            file,
+            "-User=\"{}\" -Password=\"{}\"",
+            credentials.email, credentials.password
+        )?;
+
+        Ok(CommandLineFile {
+            path,
+            source: credentials.source,
+        })
+    }
+
+    /// The game's argument for reading the file.
+    pub fn arg(&self) -> String {
+        format!("-CmdLineFile={}", self.path.display())
+    }
+}
+
+impl Drop for CommandLineFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether a line the game logged says logging in failed.
+pub fn is_auth_failure(body: &str) -> bool {
+    AUTH_FAILURE.is_match(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_login_errors() {
+        assert!(is_auth_failure(
+            "LogAuth: Error: Login failed: invalid email or password"
+        ));
+        assert!(is_auth_failure("LogAuth: Fatal: Failed to get a session"));
+    }
+
+    #[test]
+    fn ignores_other_categories_and_levels() {
+        // players being turned away and chat aren't the host failing to log in
+        assert!(!is_auth_failure(
+            "LogServerList: Error: Authentication denied for player Bob"
+        ));
+        assert!(!is_auth_failure("LogChat: Alice: login failed again lol"));
+        assert!(!is_auth_failure("LogAuth: Logged in as host"));
+        assert!(!is_auth_failure("LogAuth: Warning: Retrying login"));
+        assert!(!is_auth_failure(
+            "LogTemp: LogAuth: Error: quoted from somewhere else"
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::files;
+
+/// A ban issued by a plugin.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Ban {
+    pub uuid: Uuid,
+    pub name: String,
+    pub reason: String,
+    /// The plugin that issued the ban.
+    pub issuer: String,
+    /// When the ban was issued, in seconds since the unix epoch.
+    pub created: u64,
+    /// When the ban expires, in seconds since the unix epoch, or None if it is permanent.
+    pub expires: Option<u64>,
+}
+
+impl Ban {
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires, Some(expires) if expires <= now())
+    }
+}
+
+/// The wrapper's own list of banned players, persisted as JSON under the data folder.
+#[derive(Clone)]
+pub struct BanList {
+    path: PathBuf,
+    bans: Arc<Mutex<Vec<Ban>>>,
+}
+
+impl BanList {
+    /// Loads the ban list at `path`, or starts an empty one if it doesn't exist yet. Bans that
+    /// have expired are dropped.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut bans: Vec<Ban> = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        bans.retain(|b| !b.is_expired());
+
+        Ok(BanList {
+            path: path.into(),
+            bans: Arc::new(Mutex::new(bans)),
+        })
+    }
+
+    /// Adds a ban, replacing any existing ban for the same player.
+    pub fn ban(&self, ban: Ban) -> Result<()> {
+        let mut bans = self.bans.lock().unwrap();
+        bans.retain(|b| b.uuid != ban.uuid && !b.is_expired());
+        bans.push(ban);
+        save(&self.path, &bans)
+    }
+
+    /// Lifts the ban on a player by UUID or name, returning it if there was one. Bans issued by
+    /// UUID alone have no name, so they can only be lifted by UUID.
+    pub fn unban(&self, target: &str) -> Result<Option<Ban>> {
+        let target = target.trim();
+        if target.is_empty() {
+            return Ok(None);
+        }
+
+        let mut bans = self.bans.lock().unwrap();
+        // an expired ban isn't one to lift
+        bans.retain(|b| !b.is_expired());
+        let uuid = target.parse::<Uuid>().ok();
+        let index = bans.iter().position(|b| {
+            Some(b.uuid) == uuid || (!b.name.is_empty() && b.name.eq_ignore_ascii_case(target))
+        });
+
+        match index {
+            Some(index) => {
+                let ban = bans.remove(index);
+                save(&self.path, &bans)?;
+                Ok(Some(ban))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the active ban on a player, if any.
+    pub fn find(&self, uuid: &Uuid) -> Option<Ban> {
+        self.bans
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|b| &b.uuid == uuid && !b.is_expired())
+            .cloned()
+    }
+}
+
+/// The current time in seconds since the unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn save(path: &Path, bans: &[Ban]) -> Result<()> {
+    files::atomic_write(path, serde_json::to_string_pretty(bans)?.as_bytes())
+}
@@ -0,0 +1,245 @@
+//! A stand-in for the Brickadia server, used to test the wrapper without the game.
+//!
+//! It plays back a script of log lines in the game's log format, records every line
+//! written to its stdin, and responds to a handful of console commands.
+//!
+//! Script lines (blank lines and lines starting with `#` are ignored):
+//!
+//! ```text
+//! wait <milliseconds>
+//! join <name> <uuid>
+//! chat <name> <message...>
+//! leave <name>
+//! log <raw log body>
+//! crash <exit code>
+//! ```
+//!
+//! The files it records to can contain `{user_dir}`, which is replaced with the `-UserDir` the
+//! wrapper passes, so several fake servers run by one wrapper record to their own files.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::{self, File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::Write,
+    process::exit,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::{App, AppSettings, Arg};
+use tokio::{
+    io::{self, AsyncBufReadExt},
+    time::sleep,
+};
+use uuid::Uuid;
+
+/// Writes log lines to stdout in the game's `[timestamp][frame]Body` format.
+#[derive(Default)]
+struct GameLog {
+    frame: AtomicI32,
+}
+
+impl GameLog {
+    /// Logs a group of lines under the same frame index, like the game does for related lines.
+    fn group(&self, bodies: &[String]) {
+        let frame = self.frame.fetch_add(1, Ordering::SeqCst) % 1000;
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let timestamp = format!(
+            "2021.07.08-{:02}.{:02}.{:02}:{:03}",
+            elapsed.as_secs() / 3600 % 24,
+            elapsed.as_secs() / 60 % 60,
+            elapsed.as_secs() % 60,
+            elapsed.subsec_millis()
+        );
+
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        for body in bodies {
+            writeln!(stdout, "[{}][{:>3}]{}", timestamp, frame, body).unwrap();
+        }
+        stdout.flush().unwrap();
+    }
+
+    fn line(&self, body: String) {
+        self.group(&[body]);
+    }
+}
+
+/// Derives a stable UUID from a player's name, for scripts that don't specify one.
+fn name_uuid(name: &str, kind: &str) -> Uuid {
+    let mut hasher = DefaultHasher::new();
+    (name, kind).hash(&mut hasher);
+    let hash = hasher.finish() as u128;
+
+    Uuid::from_u128(hash << 64 | hash)
+}
+
+/// Disconnects a player, returning false if they weren't connected.
+fn leave(log: &GameLog, players: &Mutex<HashMap<String, Uuid>>, name: &str) -> bool {
+    let uuid = match players.lock().unwrap().remove(name) {
+        Some(uuid) => uuid,
+        None => return false,
+    };
+
+    log.line(format!(
+        "LogNet: UNetConnection::Close: [UNetConnection] RemoteAddr: 127.0.0.1:7777, Name: IpConnection_0, Driver: GameNetDriver IpNetDriver_0, IsServer: YES, PC: BP_PlayerController_C_0, Owner: BP_PlayerController_C_0, UniqueId: BRICKADIA:{}",
+        uuid
+    ));
+    true
+}
+
+/// Runs the script, keeping track of the players that have joined.
+async fn play(script: String, log: Arc<GameLog>, players: Arc<Mutex<HashMap<String, Uuid>>>) {
+    for line in script.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (command, rest) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            "wait" => {
+                let millis = rest.parse().expect("wait expects milliseconds");
+                sleep(Duration::from_millis(millis)).await;
+            }
+            "join" => {
+                let mut words = rest.split_whitespace();
+                let name = words.next().expect("join expects a name").to_owned();
+                let uuid: Uuid = match words.next() {
+                    Some(uuid) => uuid.parse().expect("join expects a valid uuid"),
+                    None => name_uuid(&name, "user"),
+                };
+
+                log.group(&[
+                    "LogServerList: Auth payload valid. Result:".into(),
+                    format!("LogServerList: UserName: {}", name),
+                    format!("LogServerList: UserId: {}", uuid),
+                    format!("LogServerList: HandleId: {}", name_uuid(&name, "handle")),
+                ]);
+                players.lock().unwrap().insert(name, uuid);
+            }
+            "chat" => {
+                let (name, message) = rest.split_at(rest.find(' ').unwrap_or(rest.len()));
+                log.line(format!("LogChat: {}: {}", name, message.trim_start()));
+            }
+            "leave" => assert!(
+                leave(&log, &players, rest),
+                "leave expects a player that joined"
+            ),
+            "log" => log.line(rest.into()),
+            "crash" => {
+                log.line("LogCore: Error: Fake crash".into());
+                exit(rest.parse().expect("crash expects an exit code"));
+            }
+            _ => panic!("unknown script command {:?}", command),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = App::new("fake_brickadia")
+        .about("Pretends to be a Brickadia server for testing the wrapper")
+        .setting(AppSettings::AllowLeadingHyphen)
+        .arg(
+            Arg::with_name("script")
+                .long("script")
+                .takes_value(true)
+                .help("A script of log lines to play back"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .takes_value(true)
+                .help("A file to record every line received on stdin to"),
+        )
+        .arg(
+            Arg::with_name("record-args")
+                .long("record-args")
+                .takes_value(true)
+                .help("A file to write the arguments the wrapper passes to, one per line"),
+        )
+        .arg(
+            Arg::with_name("game-args")
+                .multiple(true)
+                .allow_hyphen_values(true)
+                .help("Arguments the wrapper passes to the game, which are only recorded"),
+        )
+        .get_matches();
+
+    let args: Vec<&str> = matches
+        .values_of("game-args")
+        .into_iter()
+        .flatten()
+        .collect();
+    let user_dir = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("-UserDir="))
+        .unwrap_or(".");
+    let expand = |path: &str| path.replace("{user_dir}", user_dir);
+
+    if let Some(path) = matches.value_of("record-args") {
+        fs::write(expand(path), args.join("\n")).expect("Failed to record the arguments");
+    }
+
+    let mut record = matches.value_of("record").map(|path| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(expand(path))
+            .expect("Failed to open record file")
+    });
+
+    let log = Arc::new(GameLog::default());
+    let players = Arc::new(Mutex::new(HashMap::new()));
+
+    log.line("LogInit: Fake Brickadia server starting".into());
+
+    if let Some(path) = matches.value_of("script") {
+        let script = fs::read_to_string(path).expect("Failed to read script");
+        tokio::spawn(play(script, log.clone(), players.clone()));
+    }
+
+    let mut lines = io::BufReader::new(io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(file) = record.as_mut() {
+            record_line(file, &line);
+        }
+
+        let command = line.split_whitespace().next().unwrap_or("");
+        match command {
+            "" | "Chat.Broadcast" | "Chat.Whisper" => (),
+            "Bricks.Save" => log.line(format!(
+                "LogBrickSerializer: Saved {}",
+                line["Bricks.Save".len()..].trim()
+            )),
+            "Chat.Command" => {
+                // only /Kick "name" "reason" is understood
+                let mut quoted = line.split('"').skip(1).step_by(2);
+                match (line.split_whitespace().nth(1), quoted.next()) {
+                    (Some("/Kick"), Some(name)) if leave(&log, &players, name) => (),
+                    _ => log.line(format!("Cmd: Command not recognized: {}", line)),
+                }
+            }
+            "exit" => {
+                log.line("LogExit: Exiting.".into());
+                exit(0);
+            }
+            _ => log.line(format!("Cmd: Command not recognized: {}", line)),
+        }
+    }
+}
+
+fn record_line(file: &mut File, line: &str) {
+    writeln!(file, "{}", line).expect("Failed to write to record file");
+    file.flush().unwrap();
+}
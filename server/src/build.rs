@@ -0,0 +1,243 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::{error, info, warn, Level};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+};
+
+use crate::{
+    config,
+    logs::PluginLog,
+    plugins::{self, PluginConfig},
+};
+
+/// Builds the plugin if it has a build command and its target is missing or older than its sources.
+pub async fn build_if_needed(config: &PluginConfig) -> Result<()> {
+    if config.plugin().build().is_some() && needs_build(config) {
+        build(config).await
+    } else {
+        Ok(())
+    }
+}
+
+/// Folders that hold build output, dependencies or caches rather than sources.
+const NOT_SOURCES: &[&str] = &[
+    "target",
+    "dist",
+    "build",
+    "out",
+    "node_modules",
+    "__pycache__",
+];
+
+/// Whether the plugin's target is missing or older than any of its sources.
+pub fn needs_build(config: &PluginConfig) -> bool {
+    let target = match config.target_path() {
+        Some(target) => target,
+        None => return false,
+    };
+
+    match fs::metadata(&target).and_then(|m| m.modified()) {
+        Ok(built) => newest_source(config).is_some_and(|source| source > built),
+        Err(_) => true,
+    }
+}
+
+/// The last time one of the plugin's sources changed. Those are the files and folders its
+/// manifest lists in `sources`, or else everything in its folder except hidden files, the
+/// target itself and folders like `target` and `node_modules`.
+pub fn newest_source(config: &PluginConfig) -> Option<SystemTime> {
+    SourceFiles::default().newest(config)
+}
+
+/// A plugin's source files, remembered so that checking them again only has to look at each one
+/// rather than walk the plugin's folder. The folder is walked again once a folder in it gains or
+/// loses an entry, or the plugin's `sources` change.
+#[derive(Default)]
+pub struct SourceFiles {
+    sources: Vec<String>,
+    /// The folders that were walked, with when they were last modified at the time.
+    folders: Vec<(PathBuf, Option<SystemTime>)>,
+    files: Vec<PathBuf>,
+}
+
+impl SourceFiles {
+    /// The last time one of the plugin's sources changed (see [`newest_source`]).
+    pub fn newest(&mut self, config: &PluginConfig) -> Option<SystemTime> {
+        let stale = self.folders.is_empty()
+            || self.sources != config.plugin().sources()
+            || self
+                .folders
+                .iter()
+                .any(|(folder, walked)| modified(folder) != *walked);
+        if stale {
+            *self = SourceFiles::walk(config);
+        }
+
+        self.files.iter().filter_map(|file| modified(file)).max()
+    }
+
+    fn walk(config: &PluginConfig) -> Self {
+        let sources = config.plugin().sources();
+        let mut walked = SourceFiles {
+            sources: sources.to_vec(),
+            ..Default::default()
+        };
+        let (dir, target) = match (config.path(), config.target_path()) {
+            (Some(dir), Some(target)) => (dir, target),
+            _ => return walked,
+        };
+
+        if sources.is_empty() {
+            walked.walk_folder(dir, &target, true);
+        }
+        for path in sources.iter().map(|source| dir.join(source)) {
+            if path.is_dir() {
+                walked.walk_folder(&path, &target, false);
+            } else {
+                // a listed file that doesn't exist yet is noticed once it does
+                walked.files.push(path);
+            }
+        }
+        walked
+    }
+
+    /// Adds the files in `dir`, skipping hidden files and `target`, and build output and
+    /// dependency folders too if `skip_output`.
+    fn walk_folder(&mut self, dir: &Path, target: &Path, skip_output: bool) {
+        self.folders.push((dir.to_path_buf(), modified(dir)));
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || path == target {
+                continue;
+            }
+
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => {
+                    if skip_output && NOT_SOURCES.contains(&name.as_ref()) {
+                        continue;
+                    }
+                    self.walk_folder(&path, target, skip_output);
+                }
+                Ok(kind) if kind.is_file() => self.files.push(path),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Runs the plugin's build command in its folder, sending its output to the plugin's log.
+pub async fn build(config: &PluginConfig) -> Result<()> {
+    let command = match config.plugin().build() {
+        Some(command) => command,
+        None => bail!("the plugin has no build command"),
+    };
+    let dir = match config.path() {
+        Some(dir) => dir,
+        None => bail!("no plugin path found"),
+    };
+
+    info!("Building plugin {} ({})", config.id(), command);
+    let log = PluginLog::new(config.instance_data_dir(), &config.id());
+    log.write(Level::Info, &format!("Building: {}", command));
+
+    let mut child = shell(command)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("failed to run the build command: {}", e))?;
+
+    let stdout = tokio::spawn(copy_lines(child.stdout.take().unwrap(), log.clone()));
+    let stderr = tokio::spawn(copy_lines(child.stderr.take().unwrap(), log.clone()));
+    let status = child.wait().await?;
+    let _ = tokio::join!(stdout, stderr);
+
+    log.write(Level::Info, &format!("Build finished ({})", status));
+    if !status.success() {
+        bail!(
+            "the build command failed ({}), see {}",
+            status,
+            log.path().display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Writes each line from a build's output to the plugin's log.
+async fn copy_lines(output: impl AsyncRead + Unpin, log: PluginLog) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        log.write(Level::Info, &line);
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// Runs `server plugin build`, building the plugin with the given id or every plugin with a
+/// build command. Returns the exit code, which is 1 if any build failed.
+pub async fn run(id: Option<&str>) -> i32 {
+    let settings = config::get();
+    let configs: Vec<PluginConfig> = plugins::scan(&settings.plugins_dir, &settings.data_dir)
+        .await
+        .into_iter()
+        .filter(|c| id.map_or(c.plugin().build().is_some(), |id| c.id() == id))
+        .collect();
+
+    if configs.is_empty() {
+        match id {
+            Some(id) => error!("No plugin {} in the plugins folder", id),
+            None => warn!("No plugins have a build command"),
+        }
+        return if id.is_some() { 1 } else { 0 };
+    }
+
+    let mut failed = false;
+    for config in configs {
+        match build(&config).await {
+            Ok(()) => info!("Built plugin {}", config.id()),
+            Err(e) => {
+                error!("Plugin {} failed to build: {}", config.id(), e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{error, info, warn};
+
+use crate::{config, options, plugins::PluginConfig};
+
+/// What's wrong with a plugin's manifest. Errors keep the plugin from starting, warnings don't.
+#[derive(Default)]
+pub struct Report {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Checks the plugin in `path`, which is its folder or its plugin.toml.
+pub fn check(path: &Path) -> Report {
+    let mut report = Report::default();
+
+    let (dir, manifest) = if path.is_dir() {
+        (path.to_path_buf(), path.join("plugin.toml"))
+    } else {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        (dir, path.to_path_buf())
+    };
+
+    let contents = match fs::read_to_string(&manifest) {
+        Ok(contents) => contents,
+        Err(e) => {
+            report
+                .errors
+                .push(format!("failed to read {}: {}", manifest.display(), e));
+            return report;
+        }
+    };
+
+    // the toml crate's errors say where in the file the problem is
+    let (config, unknown) = match parse(&contents) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return report;
+        }
+    };
+
+    for key in unknown {
+        report.warnings.push(format!("unknown key {}", key));
+    }
+    report
+        .errors
+        .extend(options::check_defaults(config.options()));
+
+    match config.plugin().command() {
+        // a bare name like `node` is found on the PATH
+        Some(command) if Path::new(command).components().count() == 1 => {
+            if !on_path(command) {
+                report
+                    .warnings
+                    .push(format!("command {} isn't on the PATH", command));
+            }
+        }
+        Some(command) => {
+            if let Err(problem) = check_executable("command", &dir.join(command)) {
+                report.errors.push(problem);
+            }
+        }
+        None => {
+            // a plugin with a build command gets its target built before it starts
+            let target = dir.join(config.plugin().target());
+            match (check_executable("target", &target), config.plugin().build()) {
+                (Ok(()), _) => (),
+                (Err(_), Some(_)) if !target.exists() => report
+                    .warnings
+                    .push(format!("target {} will be built", target.display())),
+                (Err(problem), _) => report.errors.push(problem),
+            }
+        }
+    }
+
+    for source in config.plugin().sources() {
+        if !dir.join(source).exists() {
+            report.warnings.push(format!(
+                "source {} doesn't exist",
+                dir.join(source).display()
+            ));
+        }
+    }
+
+    if let Some(cwd) = config.plugin().cwd() {
+        if !dir.join(cwd).is_dir() {
+            report
+                .errors
+                .push(format!("cwd {} isn't a folder", dir.join(cwd).display()));
+        }
+    }
+
+    report
+}
+
+/// Parses a plugin.toml, also returning the keys in it that the server doesn't know about, which
+/// are usually typos.
+pub fn parse(contents: &str) -> Result<(PluginConfig, Vec<String>), toml::de::Error> {
+    let mut unknown = vec![];
+    let config = serde_ignored::deserialize(toml::Deserializer::new(contents), |path| {
+        unknown.push(path.to_string())
+    })?;
+    Ok((config, unknown))
+}
+
+fn check_executable(kind: &str, path: &Path) -> Result<(), String> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Err(format!("{} {} doesn't exist", kind, path.display())),
+    };
+    if !metadata.is_file() {
+        return Err(format!("{} {} isn't a file", kind, path.display()));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(format!("{} {} isn't executable", kind, path.display()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a program can be found in one of the folders on the PATH.
+fn on_path(program: &str) -> bool {
+    let path = match std::env::var_os("PATH") {
+        Some(path) => path,
+        None => return false,
+    };
+    std::env::split_paths(&path)
+        .any(|dir| dir.join(program).is_file() || dir.join(format!("{}.exe", program)).is_file())
+}
+
+/// Runs `server plugin check`, checking the plugin at `path` or every plugin in the plugins folder.
+/// Returns the exit code, which is 1 if any plugin has errors.
+pub fn run(path: Option<&str>) -> i32 {
+    let paths: Vec<PathBuf> = match path {
+        Some(path) => vec![path.into()],
+        None => {
+            let mut paths: Vec<PathBuf> = fs::read_dir(&config::get().plugins_dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path())
+                        .filter(|path| path.join("plugin.toml").is_file())
+                        .collect()
+                })
+                .unwrap_or_default();
+            paths.sort();
+            paths
+        }
+    };
+
+    if paths.is_empty() {
+        warn!("No plugins to check");
+        return 0;
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let report = check(&path);
+        for warning in report.warnings.iter() {
+            warn!("{}: {}", path.display(), warning);
+        }
+        for error in report.errors.iter() {
+            error!("{}: {}", path.display(), error);
+        }
+
+        if report.errors.is_empty() {
+            info!("{} is ok", path.display());
+        } else {
+            failed = true;
+        }
+    }
+
+    if failed {
+        1
+    } else {
+        0
+    }
+}
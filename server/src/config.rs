@@ -0,0 +1,514 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::LevelFilter;
+use plugin::RestartPolicy;
+use serde::{Deserialize, Serialize};
+
+/// The config file read from the working directory unless `--config` says otherwise.
+pub const CONFIG_FILE: &str = "brixide.toml";
+/// The prefix of the environment variables that override the config file.
+const ENV_PREFIX: &str = "BRIXIDE";
+
+/// The keys brixide.toml can have, and their types. Each can also be set with an environment
+/// variable named after it, e.g. `BRIXIDE_GAME_ARGS` for `game.args`.
+const KEYS: &[(&str, Kind)] = &[
+    ("data_dir", Kind::String),
+    ("plugins_dir", Kind::String),
+    ("port", Kind::Integer),
+    ("server.name", Kind::String),
+    ("server.description", Kind::String),
+    ("server.password", Kind::String),
+    ("server.max_players", Kind::Integer),
+    ("server.public", Kind::Boolean),
+    ("server.welcome_message", Kind::String),
+    ("game.command", Kind::String),
+    ("game.args", Kind::Words),
+    ("game.verbose", Kind::Boolean),
+    ("game.max_crashes", Kind::Integer),
+    ("game.crash_window", Kind::Integer),
+    ("auth.credentials_file", Kind::String),
+    ("launcher.version", Kind::String),
+    ("launcher.url", Kind::String),
+    ("launcher.sha256", Kind::String),
+    ("launcher.allow_unverified", Kind::Boolean),
+    ("log.level", Kind::String),
+    ("log.color", Kind::Boolean),
+    ("plugins.watch", Kind::Boolean),
+    ("plugins.restart", Kind::String),
+    ("plugins.max_restarts", Kind::Integer),
+    ("plugins.crash_window", Kind::Integer),
+    ("plugins.grace_period", Kind::Integer),
+];
+
+/// How an environment variable's value is read.
+#[derive(Clone, Copy)]
+enum Kind {
+    String,
+    Integer,
+    Boolean,
+    /// A list of strings, separated by whitespace.
+    Words,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The wrapper's configuration, merged from its defaults, brixide.toml, `BRIXIDE_*` environment
+/// variables and command line flags, each overriding the one before.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Where the launcher, the game's saves and the wrapper's own data live.
+    pub data_dir: PathBuf,
+    /// Where plugins are installed, one folder each.
+    pub plugins_dir: PathBuf,
+    /// The port the game listens on.
+    pub port: u16,
+    pub server: ServerSettings,
+    pub game: GameConfig,
+    pub auth: AuthConfig,
+    pub launcher: LauncherConfig,
+    pub log: LogConfig,
+    pub plugins: PluginDefaults,
+    /// Game servers to run side by side, by name. Without any, the wrapper runs a single one
+    /// from the settings above.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub instances: BTreeMap<String, InstanceConfig>,
+}
+
+/// The game's own server settings, which are written to its settings file before it starts.
+/// Settings left out keep whatever the file already has.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ServerSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The password players need to join, or an empty string for none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_players: Option<u32>,
+    /// Whether the server is listed on the public server list.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub welcome_message: Option<String>,
+}
+
+/// One of several game servers run by the same wrapper. Anything left out is taken from the top
+/// level of the config, except for the data folder, which defaults to `instances/<name>` inside
+/// the top level one.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct InstanceConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Extra arguments passed to this instance's game, after `game.args`.
+    pub args: Vec<String>,
+    pub server: ServerSettings,
+    pub auth: AuthConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GameConfig {
+    /// A command to run instead of the Brickadia launcher, e.g. fake_brickadia for testing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Extra arguments passed to the game.
+    pub args: Vec<String>,
+    /// Whether to show every line of the game's output.
+    pub verbose: bool,
+    /// How many times the game may crash within `crash_window` before the wrapper gives up.
+    pub max_crashes: usize,
+    /// The window, in seconds, over which the game's crashes are counted.
+    pub crash_window: u64,
+}
+
+/// Where the account the game hosts with comes from, until the game has logged in once.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// A TOML file with the account's `email` and `password`, which only its owner may read.
+    /// Defaults to credentials.toml in the data folder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credentials_file: Option<PathBuf>,
+}
+
+/// Which Brickadia launcher to install, and how to check it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LauncherConfig {
+    /// The launcher version to install. Pinning one replaces an installed launcher of another
+    /// version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Where to download the launcher from, instead of its usual place.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The SHA-256 digest the downloaded archive must have, for versions the wrapper doesn't know.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Whether to install an archive there's no SHA-256 for, which is otherwise refused.
+    pub allow_unverified: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogConfig {
+    /// The most detailed level the wrapper logs: off, error, warn, info, debug or trace.
+    pub level: String,
+    /// Whether log lines are colored.
+    pub color: bool,
+}
+
+/// Settings for every plugin, some of which a plugin's own plugin.toml can override.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PluginDefaults {
+    /// Whether to reload plugins when their plugin.toml or target changes.
+    pub watch: bool,
+    /// The restart policy of plugins that don't set their own.
+    pub restart: RestartPolicy,
+    /// How many times a plugin may be restarted within `crash_window` before it's left stopped.
+    pub max_restarts: usize,
+    /// The window, in seconds, over which a plugin's restarts are counted.
+    pub crash_window: u64,
+    /// How long, in seconds, plugins get to exit by themselves after being told to shut down.
+    pub grace_period: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            data_dir: "data".into(),
+            plugins_dir: "plugins".into(),
+            port: 7777,
+            server: ServerSettings::default(),
+            game: GameConfig::default(),
+            auth: AuthConfig::default(),
+            launcher: LauncherConfig::default(),
+            log: LogConfig::default(),
+            plugins: PluginDefaults::default(),
+            instances: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            command: None,
+            args: vec![],
+            verbose: false,
+            max_crashes: 5,
+            crash_window: 600,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "debug".into(),
+            color: true,
+        }
+    }
+}
+
+impl Default for PluginDefaults {
+    fn default() -> Self {
+        PluginDefaults {
+            watch: false,
+            restart: RestartPolicy::default(),
+            max_restarts: 5,
+            crash_window: 600,
+            grace_period: 5,
+        }
+    }
+}
+
+impl Config {
+    pub fn log_level(&self) -> LevelFilter {
+        self.log.level.parse().unwrap_or(LevelFilter::Debug)
+    }
+
+    /// The credentials file to read the account from.
+    pub fn credentials_file(&self) -> PathBuf {
+        match &self.auth.credentials_file {
+            Some(path) => path.clone(),
+            None => self.data_dir.join(crate::auth::CREDENTIALS_FILE),
+        }
+    }
+
+    /// The launcher to install.
+    pub fn launcher_source(&self) -> launcher::Source {
+        launcher::Source {
+            version: self.launcher.version.clone(),
+            url: self.launcher.url.clone(),
+            sha256: self.launcher.sha256.clone(),
+            allow_unverified: self.launcher.allow_unverified,
+        }
+    }
+
+    /// The game servers to run, each named and with its instance's settings in place of the top
+    /// level ones, or just this config unnamed if there are no instances.
+    pub fn instances(&self) -> Result<Vec<(Option<String>, Config)>> {
+        if self.instances.is_empty() {
+            return Ok(vec![(None, self.clone())]);
+        }
+
+        let mut resolved: Vec<(Option<String>, Config)> = vec![];
+        for (name, instance) in &self.instances {
+            // the name is used in console commands and folder names
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                bail!(
+                    "invalid instance name {:?}, use letters, numbers, - and _",
+                    name
+                );
+            }
+
+            let mut config = self.clone();
+            config.instances.clear();
+            config.data_dir = match &instance.data_dir {
+                Some(data_dir) => data_dir.clone(),
+                None => self.data_dir.join("instances").join(name),
+            };
+            if let Some(plugins_dir) = &instance.plugins_dir {
+                config.plugins_dir = plugins_dir.clone();
+            }
+            config.port = instance.port.unwrap_or(self.port);
+            config.game.args.extend(instance.args.iter().cloned());
+            config.server = instance.server.clone().or(&self.server);
+            if instance.auth.credentials_file.is_some() {
+                config.auth = instance.auth.clone();
+            }
+
+            for (other, other_config) in &resolved {
+                let other = other.as_deref().unwrap_or_default();
+                if other_config.port == config.port {
+                    bail!(
+                        "instances {} and {} both use port {}",
+                        other,
+                        name,
+                        config.port
+                    );
+                }
+                if other_config.data_dir == config.data_dir {
+                    bail!(
+                        "instances {} and {} both use the data folder {}",
+                        other,
+                        name,
+                        config.data_dir.display()
+                    );
+                }
+            }
+            resolved.push((Some(name.clone()), config));
+        }
+
+        Ok(resolved)
+    }
+}
+
+impl ServerSettings {
+    /// Replaces the password, if there is one, with a placeholder for showing the settings.
+    fn hide_password(&mut self) {
+        if let Some(password) = &mut self.password {
+            if !password.is_empty() {
+                *password = "<hidden>".into();
+            }
+        }
+    }
+
+    /// These settings, with those left out taken from `defaults`.
+    fn or(self, defaults: &ServerSettings) -> Self {
+        let defaults = defaults.clone();
+        ServerSettings {
+            name: self.name.or(defaults.name),
+            description: self.description.or(defaults.description),
+            password: self.password.or(defaults.password),
+            max_players: self.max_players.or(defaults.max_players),
+            public: self.public.or(defaults.public),
+            welcome_message: self.welcome_message.or(defaults.welcome_message),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn crash_window(&self) -> Duration {
+        Duration::from_secs(self.crash_window)
+    }
+}
+
+impl PluginDefaults {
+    pub fn crash_window(&self) -> Duration {
+        Duration::from_secs(self.crash_window)
+    }
+
+    pub fn grace_period(&self) -> Duration {
+        Duration::from_secs(self.grace_period)
+    }
+}
+
+/// Loads the configuration from the file at `path`, which only has to exist if `required`, then
+/// the environment, then `overrides` from the command line. Also returns warnings about the file,
+/// for logging once the logger is configured.
+pub fn load(path: &Path, required: bool, overrides: toml::Table) -> Result<(Config, Vec<String>)> {
+    let mut warnings = vec![];
+    let mut table = match fs::read_to_string(path) {
+        Ok(contents) => {
+            // parsed on its own first, so its errors point at the line they're on
+            let parsed: Result<Config, _> =
+                serde_ignored::deserialize(toml::Deserializer::new(&contents), |key| {
+                    warnings.push(format!("Unknown key {} in {}", key, path.display()))
+                });
+            if let Err(e) = parsed {
+                bail!("invalid config in {}: {}", path.display(), e);
+            }
+            toml::from_str(&contents)?
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound && !required => toml::Table::new(),
+        Err(e) => bail!("failed to read {}: {}", path.display(), e),
+    };
+
+    for (key, kind) in KEYS {
+        let name = env_name(key);
+        if let Ok(value) = env::var(&name) {
+            let value = parse_env(*kind, &name, &value)?;
+            set(&mut table, key, value);
+        }
+    }
+
+    for (key, _) in KEYS {
+        if let Some(value) = lookup(&overrides, key) {
+            set(&mut table, key, value.clone());
+        }
+    }
+
+    let config: Config = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e| anyhow!("invalid config from the environment or command line: {}", e))?;
+    if config.log.level.parse::<LevelFilter>().is_err() {
+        bail!(
+            "invalid config: log.level should be off, error, warn, info, debug or trace, not {}",
+            config.log.level
+        );
+    }
+    if config
+        .game
+        .command
+        .as_ref()
+        .is_some_and(|command| command.trim().is_empty())
+    {
+        bail!("invalid config: game.command is empty, leave it out to run the Brickadia launcher");
+    }
+
+    Ok((config, warnings))
+}
+
+/// Makes `config` the configuration every part of the wrapper sees.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// The wrapper's configuration, which is the default until `init` is called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Sets a dotted key such as `game.args` in a table, creating the tables on the way.
+pub fn set(table: &mut toml::Table, key: &str, value: toml::Value) {
+    match key.split_once('.') {
+        Some((section, rest)) => {
+            let section = table
+                .entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if !section.is_table() {
+                *section = toml::Value::Table(toml::Table::new());
+            }
+            if let toml::Value::Table(section) = section {
+                set(section, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.into(), value);
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    match key.split_once('.') {
+        Some((section, rest)) => lookup(table.get(section)?.as_table()?, rest),
+        None => table.get(key),
+    }
+}
+
+fn env_name(key: &str) -> String {
+    format!("{}_{}", ENV_PREFIX, key.replace('.', "_").to_uppercase())
+}
+
+/// Reads an environment variable's value as its key's type.
+fn parse_env(kind: Kind, name: &str, value: &str) -> Result<toml::Value> {
+    Ok(match kind {
+        Kind::String => toml::Value::String(value.into()),
+        Kind::Integer => toml::Value::Integer(
+            value
+                .parse()
+                .map_err(|_| anyhow!("{} should be a number, not {}", name, value))?,
+        ),
+        Kind::Boolean => toml::Value::Boolean(
+            value
+                .parse()
+                .map_err(|_| anyhow!("{} should be true or false, not {}", name, value))?,
+        ),
+        Kind::Words => toml::Value::Array(
+            value
+                .split_whitespace()
+                .map(|word| toml::Value::String(word.into()))
+                .collect(),
+        ),
+    })
+}
+
+/// Runs `server config show`, printing the configuration the wrapper would run with. Server
+/// passwords are hidden, so the output can be shared.
+pub fn show(config: &Config) -> i32 {
+    let mut config = config.clone();
+    config.server.hide_password();
+    for instance in config.instances.values_mut() {
+        instance.server.hide_password();
+    }
+
+    match toml::to_string(&config) {
+        Ok(text) => {
+            print!("{}", text);
+            0
+        }
+        Err(e) => {
+            log::error!("Failed to show the config: {}", e);
+            1
+        }
+    }
+}
+
+/// The path of the launcher inside the data folder.
+pub fn launcher_path() -> PathBuf {
+    launcher::launcher_path(&get().data_dir)
+}
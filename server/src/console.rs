@@ -0,0 +1,212 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    thread,
+};
+
+use log::warn;
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+use tokio::sync::mpsc;
+
+use crate::players::PlayerList;
+
+/// The wrapper-level commands, as completed by the console.
+const COMMANDS: &[&str] = &[
+    ".approve",
+    ".help",
+    ".instances",
+    ".plugins",
+    ".players",
+    ".reload",
+    ".schedules",
+    ".stop",
+    ".use",
+];
+
+/// A line typed into the operator console.
+#[derive(Debug)]
+pub enum Command {
+    Help,
+    Plugins,
+    Players,
+    /// Approve the capabilities the plugin with this id declares.
+    Approve(String),
+    /// Restart the plugin with this id, or every plugin.
+    Reload(Option<String>),
+    /// List the schedules of the plugin with this id, or of every plugin.
+    Schedules(Option<String>),
+    Stop,
+    /// A line to forward to the game's console.
+    Game(String),
+    /// A wrapper-level command that doesn't exist, or is missing its argument.
+    Unknown(String),
+}
+
+impl Command {
+    /// Parses a console line. Lines starting with `.` are wrapper commands, everything else goes to the game.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        if !line.starts_with('.') {
+            return Some(Command::Game(line.into()));
+        }
+
+        let mut words = line.split_whitespace();
+        let command = match (words.next(), words.next()) {
+            (Some(".approve"), Some(id)) => Command::Approve(id.into()),
+            (Some(".help"), _) => Command::Help,
+            (Some(".plugins"), _) => Command::Plugins,
+            (Some(".players"), _) => Command::Players,
+            (Some(".reload"), id) => Command::Reload(id.map(String::from)),
+            (Some(".schedules"), id) => Command::Schedules(id.map(String::from)),
+            (Some(".stop"), _) => Command::Stop,
+            _ => Command::Unknown(line.into()),
+        };
+
+        Some(command)
+    }
+}
+
+/// The names the console can tab-complete for one game server, kept up to date by the wrapper.
+#[derive(Clone, Default)]
+pub struct Completions {
+    pub players: PlayerList,
+    pub plugins: Arc<RwLock<Vec<String>>>,
+}
+
+struct ConsoleHelper {
+    /// Each game server's completions, by name if the wrapper runs several.
+    instances: Vec<(Option<String>, Completions)>,
+}
+
+impl ConsoleHelper {
+    /// The completions of the game server a line is addressed to with `@<name>`, or of every one.
+    fn completions<'a>(&'a self, address: Option<&str>) -> impl Iterator<Item = &'a Completions> {
+        let address = address.map(String::from);
+        self.instances
+            .iter()
+            .filter(move |(name, _)| address.is_none() || *name == address)
+            .map(|(_, completions)| completions)
+    }
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        // complete the word under the cursor, treating quotes as word boundaries for commands like Chat.Whisper "name"
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || c == '"')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let word = line[start..pos].to_lowercase();
+
+        // a line can be addressed to one of several game servers with `@<name>`
+        let (address, command, command_start) = match line.strip_prefix('@') {
+            Some(rest) => match rest.split_once(' ') {
+                Some((name, command)) => (Some(name), command, line.len() - command.len()),
+                None => (None, "", usize::MAX),
+            },
+            None => (None, line, 0),
+        };
+
+        let candidates: Vec<String> = if start == 0 && word.starts_with('@') {
+            self.instances
+                .iter()
+                .filter_map(|(name, _)| name.as_ref().map(|name| format!("@{}", name)))
+                .collect()
+        } else if start == command_start && word.starts_with('.') {
+            COMMANDS.iter().map(|c| String::from(*c)).collect()
+        } else if command.starts_with(".reload ")
+            || command.starts_with(".approve ")
+            || command.starts_with(".schedules ")
+        {
+            self.completions(address)
+                .flat_map(|c| c.plugins.read().unwrap().clone())
+                .collect()
+        } else if command.starts_with(".use ") {
+            self.instances
+                .iter()
+                .filter_map(|(name, _)| name.clone())
+                .collect()
+        } else {
+            self.completions(address)
+                .flat_map(|c| c.players.all())
+                .map(|p| p.name)
+                .collect()
+        };
+
+        let matches = candidates
+            .into_iter()
+            .filter(|c| c.to_lowercase().starts_with(&word))
+            .collect();
+
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+/// Starts the interactive prompt on the wrapper's stdin, completing names from each game server's
+/// `instances` and saving history to `history_path`. Lines are sent through the returned channel
+/// until stdin is closed.
+pub fn spawn(
+    instances: Vec<(Option<String>, Completions)>,
+    history_path: PathBuf,
+) -> mpsc::UnboundedReceiver<String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    // rustyline blocks, so it gets its own thread rather than a tokio task
+    thread::spawn(move || {
+        let mut editor: Editor<ConsoleHelper, DefaultHistory> = match Editor::new() {
+            Ok(editor) => editor,
+            Err(e) => {
+                warn!("Failed to start the console: {}", e);
+                return;
+            }
+        };
+        editor.set_helper(Some(ConsoleHelper { instances }));
+        let _ = editor.load_history(&history_path);
+
+        loop {
+            match editor.readline("> ") {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                        let _ = editor.save_history(&history_path);
+                    }
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                // ctrl-c at the prompt doesn't raise SIGINT, so it stops the server the same way
+                Err(ReadlineError::Interrupted) => {
+                    if sender.send(".stop".into()).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    receiver
+}
@@ -0,0 +1,125 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{config::ServerSettings, files};
+
+/// The section of the settings file the game keeps its general server settings in.
+const GENERAL_SECTION: &str = "Server__BP_ServerSettings_General_C BP_ServerSettings_General";
+
+#[cfg(target_os = "windows")]
+const PLATFORM_DIR: &str = "WindowsServer";
+#[cfg(not(target_os = "windows"))]
+const PLATFORM_DIR: &str = "LinuxServer";
+
+/// The game's server settings file, inside the data folder.
+pub fn settings_path(data_dir: &Path) -> PathBuf {
+    data_dir
+        .join("Saved")
+        .join("Config")
+        .join(PLATFORM_DIR)
+        .join("ServerSettings.ini")
+}
+
+/// The settings that are set, as the file's keys and values.
+fn entries(settings: &ServerSettings) -> Vec<(&'static str, String)> {
+    let bool_value = |b: bool| if b { "True" } else { "False" }.to_owned();
+
+    let mut entries = vec![];
+    if let Some(name) = &settings.name {
+        entries.push(("ServerName", name.clone()));
+    }
+    if let Some(description) = &settings.description {
+        entries.push(("ServerDescription", description.clone()));
+    }
+    if let Some(password) = &settings.password {
+        entries.push(("ServerPassword", password.clone()));
+    }
+    if let Some(max_players) = settings.max_players {
+        entries.push(("MaxPlayers", max_players.to_string()));
+    }
+    if let Some(public) = settings.public {
+        entries.push(("bPubliclyListed", bool_value(public)));
+    }
+    if let Some(welcome_message) = &settings.welcome_message {
+        entries.push(("WelcomeMessage", welcome_message.clone()));
+    }
+    entries
+}
+
+/// Writes the configured server settings into the game's settings file in `data_dir`, keeping
+/// everything else in it. Returns whether the file changed.
+pub fn apply(settings: &ServerSettings, data_dir: &Path) -> Result<bool> {
+    let entries = entries(settings);
+    if entries.is_empty() {
+        return Ok(false);
+    }
+    if let Some((key, _)) = entries.iter().find(|(_, value)| value.contains('\n')) {
+        bail!("the server setting for {} can't span several lines", key);
+    }
+
+    let path = settings_path(data_dir);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => bail!("failed to read {}: {}", path.display(), e),
+    };
+
+    let updated = set_keys(&contents, GENERAL_SECTION, &entries);
+    if updated == contents {
+        return Ok(false);
+    }
+
+    files::atomic_write(&path, updated.as_bytes())?;
+    Ok(true)
+}
+
+/// Sets keys in one section of an ini file, replacing their lines or adding them to the end of
+/// the section, and leaves every other line as it is.
+fn set_keys(contents: &str, section: &str, entries: &[(&str, String)]) -> String {
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let header = format!("[{}]", section);
+
+    let start = match lines.iter().position(|line| line.trim() == header) {
+        Some(index) => index + 1,
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.len()
+        }
+    };
+    let end = lines[start..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| start + offset);
+
+    // the game reads keys without regard to case
+    let mut missing = vec![];
+    for (key, value) in entries {
+        let existing = lines[start..end].iter().position(|line| {
+            line.split_once('=')
+                .is_some_and(|(k, _)| k.trim().eq_ignore_ascii_case(key))
+        });
+        match existing {
+            Some(offset) => lines[start + offset] = format!("{}={}", key, value),
+            None => missing.push(format!("{}={}", key, value)),
+        }
+    }
+
+    // new keys go after the section's last line, ahead of any blank lines before the next one
+    let insert_at = (start..end)
+        .rev()
+        .find(|&index| !lines[index].trim().is_empty())
+        .map_or(start, |index| index + 1);
+    lines.splice(insert_at..insert_at, missing);
+
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    updated
+}
@@ -0,0 +1,463 @@
+use std::{collections::HashMap, fs, future::Future, sync::Arc, time::Duration};
+
+use log::{debug, error, info, warn};
+use plugin::rpc;
+use regex::Regex;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+    time::{sleep_until, timeout, Instant},
+};
+
+use crate::{
+    auth::{self, CommandLineFile},
+    bans::BanList,
+    config::Config,
+    console::{Command, Completions},
+    game_settings,
+    matchers::*,
+    permissions,
+    plugins::{self, PluginContext, PluginEvent, PluginManager},
+    server::{CrashTracker, GameCommand, Server},
+    shutdown, wsl,
+};
+
+tokio::task_local! {
+    /// The name of the game server the current task works for, if the wrapper runs several.
+    static INSTANCE: Option<Arc<str>>;
+}
+
+/// The name of the game server the current task works for, if the wrapper runs several.
+pub fn current() -> Option<Arc<str>> {
+    INSTANCE.try_with(|name| name.clone()).ok().flatten()
+}
+
+/// Runs a future as part of the named game server, so what it logs says which one it's from.
+pub async fn scope<F: Future>(name: Option<String>, future: F) -> F::Output {
+    INSTANCE.scope(name.map(Arc::from), future).await
+}
+
+/// Spawns a task that stays part of the current task's game server.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(INSTANCE.scope(current(), future))
+}
+
+/// Runs one game server and its plugins until it's stopped with `.stop` from `console`, or gives up
+/// after crashing too often, returning its exit code. The game gets `credentials_file` until it
+/// has logged in. If the game can't be started at all, this logs why and returns 1.
+pub async fn run(
+    config: Config,
+    game_command: GameCommand,
+    mut credentials_file: Option<CommandLineFile>,
+    completions: Completions,
+    mut console_receiver: mpsc::UnboundedReceiver<String>,
+) -> i32 {
+    let port = config.port;
+
+    // create the data folder if it doesn't exist
+    if !config.data_dir.exists() {
+        if let Err(e) = fs::create_dir_all(&config.data_dir) {
+            error!("Failed to create the data folder: {}", e);
+            return 1;
+        }
+    }
+
+    // the game reads its settings when it starts, so they're written first
+    match game_settings::apply(&config.server, &config.data_dir) {
+        Ok(true) => info!("Updated the game's server settings"),
+        Ok(false) => (),
+        Err(e) => {
+            error!("Failed to write the game's server settings: {}", e);
+            return 1;
+        }
+    }
+
+    // prepare the stdin channel (receives info from plugins about how to send to the game's stdin)
+    let (stdin_sender, stdin_receiver) = mpsc::unbounded_channel::<String>();
+
+    let players = completions.players.clone();
+    let bans = match BanList::load(&config.data_dir.join("bans.json")) {
+        Ok(bans) => bans,
+        Err(e) => {
+            error!("Failed to load the ban list: {}", e);
+            return 1;
+        }
+    };
+
+    // a stream of plugins exiting by themselves or asking to be reloaded, handled by the manager
+    let (plugin_event_sender, mut plugin_event_receiver) = mpsc::unbounded_channel::<PluginEvent>();
+
+    let plugins = plugins::scan(&config.plugins_dir, &config.data_dir).await;
+    let mut plugin_manager = PluginManager::new(PluginContext {
+        plugins_dir: config.plugins_dir.clone(),
+        data_dir: config.data_dir.clone(),
+        stdin: stdin_sender.clone(),
+        players: players.clone(),
+        bans: bans.clone(),
+        events: plugin_event_sender.clone(),
+    });
+    plugin_manager.start_all(plugins).await;
+
+    info!("Started {} plugins", plugin_manager.instances().len());
+
+    if config.plugins.watch {
+        info!("Watching the plugins folder for changes");
+        plugins::watch(config.plugins_dir.clone(), plugin_event_sender);
+    }
+
+    *completions.plugins.write().unwrap() = plugin_manager.ids();
+
+    // check if we're rocking WSL, and if we are, start the udp proxy
+    let mut _udp_proxy: Option<wsl::UdpProxy> = None;
+
+    if wsl::is_wsl() {
+        let ip = match wsl::ip().await {
+            Ok(ip) => ip,
+            Err(e) => {
+                error!("Failed to get the WSL IP: {}", e);
+                shutdown::stop_plugins(plugin_manager.instances()).await;
+                return 1;
+            }
+        };
+        info!("Detected WSL, starting UDP proxy on {}", ip);
+        match wsl::UdpProxy::spawn(ip, port.into()).await {
+            Ok(proxy) => _udp_proxy = Some(proxy),
+            Err(e) => {
+                error!("Failed to start the UDP proxy: {}", e);
+                shutdown::stop_plugins(plugin_manager.instances()).await;
+                return 1;
+            }
+        }
+    }
+
+    // at this point the launcher should be installed, so we can create a server instance and start reading from it
+    let mut launch_args = vec![format!("-port={}", port)];
+    launch_args.extend(config.game.args.iter().cloned());
+    let with_credentials = |args: &[String], file: &Option<CommandLineFile>| {
+        let mut args = args.to_vec();
+        args.extend(file.as_ref().map(CommandLineFile::arg));
+        args
+    };
+
+    let is_server_verbose = config.game.verbose;
+    let stdin_receiver = Arc::new(Mutex::new(stdin_receiver));
+    let mut server = match Server::start(
+        &game_command,
+        &with_credentials(&launch_args, &credentials_file),
+        &config.data_dir,
+        stdin_receiver.clone(),
+    ) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("Failed to start the game ({}): {}", game_command.program, e);
+            shutdown::stop_plugins(plugin_manager.instances()).await;
+            return 1;
+        }
+    };
+
+    info!("Server active");
+
+    let mut lines = server.lines();
+
+    // restart the game when it exits on its own, until it crashes too often
+    let max_crashes = config.game.max_crashes;
+    let crash_window = config.game.crash_window;
+    let mut crashes = CrashTracker::new(max_crashes, config.game.crash_window());
+    let mut restart_at: Option<Instant> = None;
+
+    let log_matcher =
+        Regex::new("^\\[[\\d\\.\\-:]+\\]\\[\\s*(?P<index>\\d+)\\](?P<body>.+)$").unwrap();
+
+    // a stream to handle sending rpc messages to plugins
+    let (plugin_rpc_sender, mut plugin_rpc_receiver) = mpsc::unbounded_channel::<rpc::Message>();
+
+    let grouped_regex_matchers: Vec<Arc<dyn GroupedRegexMatcher + Send>> = vec![
+        Arc::new(ChatRegexMatcher(plugin_rpc_sender.clone())),
+        Arc::new(ConnectRegexMatcher {
+            rpc: plugin_rpc_sender.clone(),
+            game_stdin: stdin_sender.clone(),
+            players: players.clone(),
+            bans: bans.clone(),
+        }),
+        Arc::new(LeaveRegexMatcher(
+            plugin_rpc_sender.clone(),
+            players.clone(),
+        )),
+    ];
+    let mut grouped_regex_instances: Vec<GroupedRegexMatches<'_>> = vec![];
+
+    // repeatedly listen to stdout for new content
+    loop {
+        tokio::select! {
+            line = lines.next_line(), if restart_at.is_none() => {
+                // line from the game server
+
+                let line = match line {
+                    Ok(Some(line)) => line,
+                    _ => {
+                        // the game exited without being asked to
+                        match server.child.wait().await {
+                            Ok(status) => warn!("The game exited unexpectedly ({})", status),
+                            Err(e) => warn!("The game exited unexpectedly: {}", e),
+                        }
+                        server.stdin_task.abort();
+                        players.clear();
+                        grouped_regex_instances.clear();
+
+                        match crashes.record() {
+                            Some(backoff) => {
+                                warn!("Restarting the game in {}s", backoff.as_secs());
+                                restart_at = Some(Instant::now() + backoff);
+                                continue;
+                            }
+                            None => {
+                                error!("The game crashed {} times in {}s, giving up", max_crashes, crash_window);
+                                shutdown::stop_plugins(plugin_manager.instances()).await;
+                                return shutdown::EXIT_CRASHED;
+                            }
+                        }
+                    }
+                };
+
+                if is_server_verbose {
+                    debug!(":: {}", line);
+                }
+
+                let log_match = match log_matcher.captures(line.as_str()) {
+                    Some(x) => x,
+                    None => continue
+                };
+
+                let index: i32 = log_match["index"].parse().unwrap();
+                let body = &log_match["body"];
+
+                // until the game has logged in, watch for it saying the credentials were wrong
+                if let Some(file) = &credentials_file {
+                    if auth::is_authenticated(&config.data_dir) {
+                        info!("The game logged in, its credentials are no longer needed");
+                        credentials_file = None;
+                    } else if auth::is_auth_failure(body) {
+                        error!("The game failed to log in with the credentials from {}: {}", file.source, body.trim());
+                        error!("Fix the credentials and start the server again");
+                        shutdown::stop(plugin_manager.instances(), &stdin_sender, &mut server).await;
+                        return shutdown::EXIT_AUTH_FAILED;
+                    }
+                }
+
+                // handle each grouped regex instance, and break if one is matched
+                let mut i: usize = 0;
+                for instance in grouped_regex_instances.iter_mut() {
+                    let index_matches = match instance.index {
+                        Some(n) => index == n,
+                        None => true
+                    };
+
+                    if index_matches {
+                        let regexes = instance.matcher.regexes();
+                        let next_regex = &regexes[instance.captures.len()];
+
+                        let capture_names = next_regex.capture_names();
+                        if let Some(captures) = next_regex.captures(body) {
+                            // update last
+                            instance.last = Instant::now();
+
+                            // clone out captures into a map for ownership
+                            let mut map = HashMap::new();
+
+                            for group_name in capture_names {
+                                let group_name = match group_name {
+                                    Some(x) => x,
+                                    None => continue
+                                };
+                                let m = captures.name(group_name).unwrap().as_str();
+                                map.insert(String::from(group_name), String::from(m));
+                            }
+
+                            // we have our map, update the instance
+                            instance.captures.push(map);
+
+                            // if our captures count is >= the regex count, our job here is done:
+                            // submit the rpc message
+                            if instance.captures.len() >= regexes.len() {
+                                instance.matcher.complete(instance).await;
+                                break;
+                            }
+                        }
+                    }
+
+                    i += 1;
+                }
+
+                // loop terminated early somewhere, so we remove it at its index
+                if i < grouped_regex_instances.len() {
+                    grouped_regex_instances.remove(i);
+                    continue;
+                }
+
+                // handle each grouped regex matcher, trying to start new instances if possible
+                for matcher in grouped_regex_matchers.iter() {
+                    let matcher_regexes = matcher.regexes();
+                    let first_regex = &matcher_regexes[0];
+
+                    let capture_names = first_regex.capture_names();
+                    if let Some(captures) = first_regex.captures(body) {
+                        // effectively clone out captures into a map for ownership
+                        let mut map = HashMap::new();
+
+                        for group_name in capture_names {
+                            let group_name = match group_name {
+                                Some(x) => x,
+                                None => continue
+                            };
+                            let m = captures.name(group_name).unwrap().as_str();
+                            map.insert(String::from(group_name), String::from(m));
+                        }
+
+                        // we match with the first regex, so let's start making a new instance
+                        let instance = GroupedRegexMatches { index: Some(index), matcher: matcher.clone(), captures: RegexCaptures::new(vec![map]), last: Instant::now(), timeout: Duration::from_secs(1) };
+
+                        // if the grouped regex actually only has one regex, we can early-terminate and avoid adding it to the array
+                        if matcher_regexes.len() == 1 {
+                            matcher.complete(&instance).await;
+                            break;
+                        } else {
+                            // add it to the instances array
+                            grouped_regex_instances.push(instance);
+                        }
+                    }
+                }
+
+                // clean up expired regex matchers if their last instant exceeds some timeout
+                grouped_regex_instances.retain(|instance| instance.last + instance.timeout > Instant::now());
+            }
+            _ = sleep_until(restart_at.unwrap_or_else(Instant::now)), if restart_at.is_some() => {
+                // time to restart the game after a crash
+
+                restart_at = None;
+
+                // lines sent while the game was down were meant for the one that crashed
+                let mut stale = 0;
+                {
+                    let mut receiver = stdin_receiver.lock().await;
+                    // a timeout polls the receiver once before it expires, taking whatever is queued
+                    while let Ok(Some(_)) = timeout(Duration::ZERO, receiver.recv()).await {
+                        stale += 1;
+                    }
+                }
+                if stale > 0 {
+                    warn!("Dropped {} lines sent to the game while it was down", stale);
+                }
+
+                match Server::start(&game_command, &with_credentials(&launch_args, &credentials_file), &config.data_dir, stdin_receiver.clone()) {
+                    Ok(restarted) => {
+                        server = restarted;
+                        lines = server.lines();
+                        info!("Server restarted");
+                        plugin_rpc_sender.send(rpc::Message::notification("server.restarted", None)).unwrap();
+                    }
+                    Err(e) => {
+                        error!("Failed to restart the game: {}", e);
+                        match crashes.record() {
+                            Some(backoff) => restart_at = Some(Instant::now() + backoff),
+                            None => {
+                                error!("The game crashed {} times in {}s, giving up", max_crashes, crash_window);
+                                shutdown::stop_plugins(plugin_manager.instances()).await;
+                                return shutdown::EXIT_CRASHED;
+                            }
+                        }
+                    }
+                }
+            }
+            Some(rpc_message) = plugin_rpc_receiver.recv() => {
+                // message from plugin rpc receiver
+
+                plugin_manager.broadcast(&rpc_message);
+            }
+            Some(event) = plugin_event_receiver.recv() => {
+                // a plugin exited by itself or asked for a reload
+
+                plugin_manager.handle(event);
+                *completions.plugins.write().unwrap() = plugin_manager.ids();
+            }
+            _ = sleep_until(plugin_manager.next_restart().unwrap_or_else(Instant::now)), if plugin_manager.next_restart().is_some() => {
+                // time to restart plugins after they exited
+
+                plugin_manager.restart_due();
+                *completions.plugins.write().unwrap() = plugin_manager.ids();
+            }
+            Some(line) = console_receiver.recv() => {
+                // line from the operator console
+
+                match Command::parse(&line) {
+                    Some(Command::Game(line)) => stdin_sender.send(line).unwrap(),
+                    Some(Command::Help) => {
+                        info!("Lines are sent to the game console, except for these commands:");
+                        info!("  .plugins          list running plugins");
+                        info!("  .approve <plugin> approve the capabilities a plugin declares");
+                        info!("  .players          list online players");
+                        info!("  .reload [plugin]  restart a plugin, or all of them, rereading the plugins folder");
+                        info!("  .schedules [plugin] list the schedules of a plugin, or of all of them");
+                        info!("  .stop             stop the server and exit");
+                    }
+                    Some(Command::Plugins) => {
+                        info!("{} plugins running", plugin_manager.instances().len());
+                        for instance in plugin_manager.instances() {
+                            let plugin = instance.config.plugin();
+                            info!("  {} - {} by {}", instance.config.id(), plugin.name(), plugin.author());
+                            let granted = instance.permissions.granted();
+                            if !granted.is_empty() {
+                                info!("    capabilities: {}", permissions::names(&granted));
+                            }
+                            let pending = instance.permissions.pending();
+                            if !pending.is_empty() {
+                                info!("    waiting for approval: {}", permissions::names(&pending));
+                            }
+                        }
+                    }
+                    Some(Command::Players) => {
+                        let online = players.all();
+                        info!("{} players online", online.len());
+                        for player in online {
+                            info!("  {} ({})", player.name, player.uuid);
+                        }
+                    }
+                    Some(Command::Approve(id)) => {
+                        if let Err(e) = plugin_manager.approve(&id).await {
+                            warn!("Failed to approve plugin {}: {}", id, e);
+                        }
+                    }
+                    Some(Command::Reload(id)) => plugin_manager.reload(id),
+                    Some(Command::Schedules(id)) => {
+                        let instances = plugin_manager
+                            .instances()
+                            .iter()
+                            .filter(|i| id.as_ref().is_none_or(|id| i.config.id() == *id));
+                        for instance in instances {
+                            let schedules = instance.schedules.list();
+                            info!("{} has {} schedules", instance.config.id(), schedules.len());
+                            for schedule in schedules {
+                                let next = match schedule.next() {
+                                    Some(next) => next.format("%Y-%m-%d %H:%M:%S").to_string(),
+                                    None => "never".into(),
+                                };
+                                let persisted = if schedule.persist { ", persisted" } else { "" };
+                                info!("  {} - {}{}, next at {}", schedule.name, schedule.trigger, persisted, next);
+                            }
+                        }
+                    }
+                    Some(Command::Stop) => break,
+                    Some(Command::Unknown(line)) => warn!("Unknown command {}, try .help", line),
+                    None => (),
+                }
+            }
+        }
+    }
+
+    let code = shutdown::stop(plugin_manager.instances(), &stdin_sender, &mut server).await;
+    info!("Server stopped");
+    code
+}
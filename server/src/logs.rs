@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::SystemTime,
+};
+
+use lazy_static::lazy_static;
+use log::{warn, Level};
+
+/// Plugin log files are rotated once they grow past this many bytes.
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+/// How many rotated files are kept for each plugin, as `<id>.log.1` (the newest) and up.
+const KEPT_LOGS: usize = 3;
+
+lazy_static! {
+    /// The log files in use, by path. A plugin that's restarted or rebuilt while its old process
+    /// is still being drained shares the old handle, so only one of them writes and rotates.
+    static ref OPEN_LOGS: Mutex<HashMap<PathBuf, Weak<Mutex<LogFile>>>> = Mutex::new(HashMap::new());
+}
+
+/// A plugin's own log file under `data/logs/plugins`, holding its `log` messages and its stderr.
+#[derive(Clone)]
+pub struct PluginLog(Arc<Mutex<LogFile>>);
+
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+    /// Set once opening the file fails, so the failure is only reported once.
+    failed: bool,
+}
+
+impl PluginLog {
+    /// The log file for the plugin with the given id, in the game server's data folder. It is
+    /// opened on the first write, and shared with any other handle on it that's still in use.
+    pub fn new(data_dir: &Path, id: &str) -> Self {
+        let path = data_dir
+            .join("logs")
+            .join("plugins")
+            .join(format!("{}.log", id));
+
+        let mut open_logs = OPEN_LOGS.lock().unwrap();
+        open_logs.retain(|_, log| log.strong_count() > 0);
+        if let Some(log) = open_logs.get(&path).and_then(Weak::upgrade) {
+            return PluginLog(log);
+        }
+
+        let log = Arc::new(Mutex::new(LogFile {
+            path: path.clone(),
+            file: None,
+            size: 0,
+            failed: false,
+        }));
+        open_logs.insert(path, Arc::downgrade(&log));
+        PluginLog(log)
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.0.lock().unwrap().path.clone()
+    }
+
+    /// Appends a line to the log, rotating it first if it has grown too large.
+    pub fn write(&self, level: Level, line: &str) {
+        let line = format!(
+            "[{}] {:<5} {}\n",
+            humantime::format_rfc3339_seconds(SystemTime::now()),
+            level,
+            line
+        );
+
+        let mut log = self.0.lock().unwrap();
+        log.open_once();
+        if log.file.is_some() && log.size + line.len() as u64 > MAX_LOG_SIZE {
+            log.rotate();
+            log.open_once();
+        }
+
+        if let Some(file) = log.file.as_mut() {
+            if file.write_all(line.as_bytes()).is_ok() {
+                log.size += line.len() as u64;
+            }
+        }
+    }
+}
+
+/// The level of a line a plugin wrote to stderr. A line that starts with a level, such as
+/// `WARNING: ...`, `[error] ...` or env_logger's `[<time> WARN  <module>] ...`, is logged at that
+/// level, and anything else at info.
+pub fn stderr_level(line: &str) -> Level {
+    let mut words = line.split_whitespace();
+    let first = words.next().unwrap_or_default();
+    let level = |word: &str| {
+        let word = word.trim_start_matches('[').split([']', ':']).next()?;
+        match word.to_ascii_lowercase().as_str() {
+            "error" | "err" | "fatal" | "critical" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    };
+
+    level(first)
+        .or_else(|| {
+            // a bracketed timestamp before the level
+            if first.starts_with('[') {
+                words.next().and_then(level)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(Level::Info)
+}
+
+impl LogFile {
+    /// Opens the file if it isn't open, reporting a failure only once.
+    fn open_once(&mut self) {
+        if self.file.is_some() || self.failed {
+            return;
+        }
+        if let Err(e) = self.open() {
+            warn!("Failed to open {}: {}", self.path.display(), e);
+            self.failed = true;
+        }
+    }
+
+    fn open(&mut self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Shifts `<id>.log` to `<id>.log.1`, `<id>.log.1` to `<id>.log.2` and so on, dropping the oldest.
+    fn rotate(&mut self) {
+        self.file = None;
+        self.size = 0;
+
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        let _ = fs::remove_file(rotated(KEPT_LOGS));
+        for n in (1..KEPT_LOGS).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        if let Err(e) = fs::rename(&self.path, rotated(1)) {
+            warn!("Failed to rotate {}: {}", self.path.display(), e);
+        }
+    }
+}
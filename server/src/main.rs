@@ -1,71 +1,202 @@
-use std::{collections::HashMap, fs, path::Path, process::exit, sync::Arc, time::Duration};
+use std::{collections::BTreeMap, fs, path::Path, process::exit};
 
-use clap::{App, Arg, SubCommand};
-use dialoguer::{theme::ColorfulTheme, Input, Password};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use fern::{
     colors::{Color, ColoredLevelConfig},
     Dispatch,
 };
-use log::{debug, error, info, warn};
-use plugin::rpc;
-use regex::Regex;
-use tokio::{
-    io::{self, AsyncBufReadExt},
-    sync::mpsc,
-    time::Instant,
-};
-
-use crate::{
-    matchers::*,
-    plugins::{PluginChannels, PluginInstance},
-    server::Server,
-};
-
+use log::{error, info, warn};
+use tokio::sync::mpsc;
+
+use crate::{config::LogConfig, console::Completions, server::GameCommand};
+
+mod auth;
+mod bans;
+mod build;
+mod check;
+mod config;
+mod console;
+mod files;
+mod game_settings;
+mod instance;
+mod logs;
 mod matchers;
+mod options;
+mod permissions;
+mod players;
 mod plugins;
+mod scaffold;
+mod schedule;
 mod server;
+mod shutdown;
+mod store;
 mod wsl;
 
-#[tokio::main]
-async fn main() {
-    // configure the logger
+fn main() {
+    // the environment is changed before the runtime starts any threads
+    auth::take_env_credentials();
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start the async runtime");
+    let code = runtime.block_on(run());
+    exit(code);
+}
+
+/// Configures the logger from the log settings.
+fn init_logger(config: &LogConfig, level: log::LevelFilter) {
     let colors = ColoredLevelConfig::new()
         .debug(Color::BrightBlue)
         .info(Color::Green)
         .warn(Color::Yellow)
         .error(Color::Red);
+    let color = config.color;
 
     Dispatch::new()
         .format(move |out, message, record| {
-            out.finish(format_args!(
-                "{} {}",
-                format!(
-                    "\x1B[{}m>>\x1B[0m",
-                    colors.get_color(&record.level()).to_fg_str()
-                ),
-                message
-            ))
+            // lines from one of several game servers say which
+            let instance = match instance::current() {
+                Some(name) => format!("[{}] ", name),
+                None => String::new(),
+            };
+            if color {
+                out.finish(format_args!(
+                    "\x1B[{}m>>\x1B[0m {}{}",
+                    colors.get_color(&record.level()).to_fg_str(),
+                    instance,
+                    message
+                ))
+            } else {
+                out.finish(format_args!(">> {}{}", instance, message))
+            }
         })
-        .level(log::LevelFilter::Debug)
+        .level(level)
+        .level_for("rustyline", log::LevelFilter::Info.min(level))
         .chain(std::io::stdout())
         .apply()
         .expect("Failed to apply logger");
+}
 
+/// The config settings given as command line flags, which override the config file and the environment.
+fn cli_overrides(matches: &ArgMatches<'_>) -> Result<toml::Table, String> {
+    let mut overrides = toml::Table::new();
+    for (flag, key) in [
+        ("data-dir", "data_dir"),
+        ("plugins-dir", "plugins_dir"),
+        ("game-command", "game.command"),
+        ("log-level", "log.level"),
+        ("credentials-file", "auth.credentials_file"),
+    ] {
+        if let Some(value) = matches.value_of(flag) {
+            config::set(&mut overrides, key, toml::Value::String(value.into()));
+        }
+    }
+    for (flag, key) in [
+        ("port", "port"),
+        ("max-crashes", "game.max_crashes"),
+        ("crash-window", "game.crash_window"),
+    ] {
+        if let Some(value) = matches.value_of(flag) {
+            let value = value
+                .parse()
+                .map_err(|_| format!("--{} should be a number, not {}", flag, value))?;
+            config::set(&mut overrides, key, toml::Value::Integer(value));
+        }
+    }
+    for (flag, key) in [
+        ("server-verbose", "game.verbose"),
+        ("watch-plugins", "plugins.watch"),
+    ] {
+        if matches.is_present(flag) {
+            config::set(&mut overrides, key, toml::Value::Boolean(true));
+        }
+    }
+    Ok(overrides)
+}
+
+/// Runs the wrapper until it is stopped, returning its exit code.
+async fn run() -> i32 {
     let matches = App::new("brust (working title)")
         .version("0.1.0")
         .author("voximity")
         .about("Server wrapper for Brickadia")
+        .arg(Arg::with_name("config")
+            .long("config")
+            .short("c")
+            .env("BRIXIDE_CONFIG")
+            .takes_value(true)
+            .help("The config file to read (defaults to brixide.toml, if it exists)"))
+        .arg(Arg::with_name("data-dir")
+            .long("data-dir")
+            .takes_value(true)
+            .help("Keep the launcher, the game's saves and the wrapper's data here"))
+        .arg(Arg::with_name("plugins-dir")
+            .long("plugins-dir")
+            .takes_value(true)
+            .help("Load plugins from here"))
+        .arg(Arg::with_name("log-level")
+            .long("log-level")
+            .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+            .help("The most detailed level to log"))
         .arg(Arg::with_name("no-install")
             .long("no-install")
             .help("Exit if the launcher is not installed"))
         .arg(Arg::with_name("port")
             .long("port")
             .short("p")
-            .help("Change the port the server will run on")
-            .default_value("7777"))
+            .takes_value(true)
+            .help("Change the port the server will run on (7777 by default)"))
         .arg(Arg::with_name("server-verbose")
             .long("server-verbose")
             .help("Display all logs from the Brickadia server"))
+        .arg(Arg::with_name("game-command")
+            .long("game-command")
+            .takes_value(true)
+            .help("Run this command instead of the Brickadia launcher (e.g. fake_brickadia for testing)"))
+        .arg(Arg::with_name("max-crashes")
+            .long("max-crashes")
+            .takes_value(true)
+            .help("Stop restarting the game after it crashes this many times within the crash window (5 by default)"))
+        .arg(Arg::with_name("crash-window")
+            .long("crash-window")
+            .takes_value(true)
+            .help("The window, in seconds, over which crashes are counted (600 by default)"))
+        .arg(Arg::with_name("watch-plugins")
+            .long("watch-plugins")
+            .help("Reload plugins when their plugin.toml or target changes"))
+        .arg(Arg::with_name("credentials-file")
+            .long("credentials-file")
+            .takes_value(true)
+            .help("Read the account's email and password from this TOML file (defaults to credentials.toml in the data folder)"))
+        .arg(Arg::with_name("credentials-stdin")
+            .long("credentials-stdin")
+            .help("Read the account's email and password from the first two lines of stdin"))
+        .subcommand(SubCommand::with_name("plugin")
+            .about("Work with plugins")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("check")
+                .about("Check plugin manifests for errors")
+                .arg(Arg::with_name("path")
+                    .help("The plugin folder or plugin.toml to check (defaults to every plugin)")))
+            .subcommand(SubCommand::with_name("build")
+                .about("Run the build command of every plugin, or of one")
+                .arg(Arg::with_name("id")
+                    .help("The plugin to build")))
+            .subcommand(SubCommand::with_name("new")
+                .about("Create a plugin in the plugins folder")
+                .arg(Arg::with_name("name")
+                    .required(true)
+                    .help("The plugin's name, which is also its folder"))
+                .arg(Arg::with_name("template")
+                    .long("template")
+                    .possible_values(scaffold::TEMPLATES)
+                    .default_value("rust")
+                    .help("The kind of plugin to create"))))
+        .subcommand(SubCommand::with_name("config")
+            .about("Work with the wrapper's configuration")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("show")
+                .about("Print the configuration after merging the config file, environment and flags")))
         .subcommand(SubCommand::with_name("install")
             .about("Forcefully install the Brickadia launcher"))
         .subcommand(SubCommand::with_name("uninstall")
@@ -75,16 +206,67 @@ async fn main() {
                 .help("You understand the consequences by running this command: your server and all its data will be lost")))
         .get_matches();
 
+    // the config file is only required if it was asked for
+    let loaded = cli_overrides(&matches)
+        .map_err(anyhow::Error::msg)
+        .and_then(|overrides| {
+            // is_present would also be true for the config subcommand
+            let given = matches.value_of("config");
+            let path = given.unwrap_or(config::CONFIG_FILE);
+            config::load(Path::new(path), given.is_some(), overrides)
+        });
+    let (config, warnings) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            init_logger(&LogConfig::default(), log::LevelFilter::Debug);
+            error!("{}", e);
+            return 1;
+        }
+    };
+    init_logger(&config.log, config.log_level());
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+    config::init(config.clone());
+
+    // config subcommands
+    if let Some(matches) = matches.subcommand_matches("config") {
+        if matches.subcommand_matches("show").is_some() {
+            return config::show(&config);
+        }
+    }
+
+    // plugin subcommands
+    if let Some(matches) = matches.subcommand_matches("plugin") {
+        if let Some(matches) = matches.subcommand_matches("check") {
+            return check::run(matches.value_of("path"));
+        }
+        if let Some(matches) = matches.subcommand_matches("build") {
+            return build::run(matches.value_of("id")).await;
+        }
+        if let Some(matches) = matches.subcommand_matches("new") {
+            return scaffold::run(
+                matches.value_of("name").unwrap(),
+                matches.value_of("template").unwrap(),
+            );
+        }
+    }
+
     // install subcommand
     if let Some(matches) = matches.subcommand_matches("install") {
-        launcher::install(matches).await;
-        exit(0);
+        if let Err(e) =
+            launcher::install(matches, &config.data_dir, &config.launcher_source()).await
+        {
+            error!("Failed to install the launcher: {}", e);
+            return 1;
+        }
+        return 0;
     }
 
     // uninstall subcommand
     if let Some(matches) = matches.subcommand_matches("uninstall") {
         if matches.is_present("i-understand") {
-            if let Err(_) = fs::remove_dir_all("data") {
+            if fs::remove_dir_all(&config.data_dir).is_err() {
                 error!(
                     "An error occurred uninstalling the server (are enough permissions granted?)"
                 );
@@ -99,235 +281,222 @@ async fn main() {
         exit(0);
     }
 
-    // run the server
-    let port: i32 = matches
-        .value_of("port")
-        .unwrap()
-        .parse()
-        .expect("Invalid port number");
+    // run the game servers
+    let game_command = match &config.game.command {
+        Some(command) => match GameCommand::parse(command) {
+            Some(command) => command,
+            None => {
+                error!("Invalid config: game.command is empty");
+                return 1;
+            }
+        },
+        None => GameCommand::launcher(),
+    };
 
-    // check if the launcher is installed. if it's not, let's install it first
-    if !launcher::is_installed(&matches) {
+    // check if the launcher is installed, and is the pinned version. if it's not, let's install it first
+    let source = config.launcher_source();
+    if config.game.command.is_none() && !launcher::is_installed(&matches, &config.data_dir, &source)
+    {
+        let launcher_exists = config::launcher_path().exists();
         if matches.is_present("no-install") {
             warn!("The launcher is not installed, exiting");
             exit(0);
         }
 
         #[cfg(not(target_os = "windows"))]
-        warn!("The launcher is not installed, it will be downloaded now");
-
-        launcher::install(&matches).await;
-    }
-
-    // if we're on windows create the data folder if it doesn't exist
-    if !Path::new("data").exists() {
-        fs::create_dir("data").expect("Unable to create data directory");
-    }
-
-    // get one-time account credentials if we don't have auth already
-    let mut credentials: (Option<String>, Option<String>) = (None, None);
-    if !Path::new("data/Saved/Auth").exists() {
-        info!(
-            "Please enter your account information to host the server (this is a one-time process)"
-        );
-
-        let email: String = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt("Account email")
-            .interact()
-            .unwrap();
-
-        let password: String = Password::with_theme(&ColorfulTheme::default())
-            .with_prompt("Account password")
-            .interact()
-            .unwrap();
-
-        credentials.0 = Some(email);
-        credentials.1 = Some(password);
-
-        warn!("If the server asks for your credentials next time, your credentials were inputted incorrectly");
-    }
-
-    // prepare the stdin channel (receives info from plugins about how to send to the game's stdin)
-    let (stdin_sender, stdin_receiver) = mpsc::unbounded_channel::<String>();
-
-    // a stream to handle new GroupedRegexMatches
-    let (new_matcher_sender, mut new_matcher_receiver) =
-        mpsc::unbounded_channel::<GroupedRegexMatches>();
+        if launcher_exists {
+            warn!(
+                "The installed launcher isn't version {}, it will be replaced now",
+                source.version()
+            );
+        } else {
+            warn!("The launcher is not installed, it will be downloaded now");
+        }
 
-    let plugins = plugins::scan().await;
-    let mut instances = vec![];
-    let plugin_channels = PluginChannels {
-        stdin: stdin_sender,
-        matchers: new_matcher_sender,
-    };
-    for plugin_config in plugins {
-        let instance = match PluginInstance::start(plugin_config, &plugin_channels) {
-            Ok(i) => i,
-            Err(x) => {
-                warn!("Plugin failed to start: {:?}", x);
-                continue;
+        if let Err(e) = launcher::install(&matches, &config.data_dir, &source).await {
+            error!("Failed to install the launcher: {}", e);
+            if !launcher_exists {
+                return 1;
             }
-        };
-        instances.push(instance);
+            warn!("Keeping the launcher that's already installed");
+        }
     }
 
-    info!("Started {} plugins", instances.len());
-
-    // check if we're rocking WSL, and if we are, start the udp proxy
-    let mut _udp_proxy: Option<wsl::UdpProxy> = None;
+    let instances = match config.instances() {
+        Ok(instances) => instances,
+        Err(e) => {
+            error!("Invalid config: {}", e);
+            return 1;
+        }
+    };
 
-    if wsl::is_wsl() {
-        let ip = wsl::ip().await.expect("Failed to get WSL IP");
-        info!("Detected WSL, starting UDP proxy on {}", ip);
-        _udp_proxy = Some(wsl::UdpProxy::spawn(ip, port).await.unwrap());
+    // get one-time account credentials for each game server that doesn't have auth already, one
+    // at a time as they may come from stdin. they're handed to the game in a file, so they don't
+    // show up in the process list
+    let mut prepared = vec![];
+    for (name, instance_config) in instances {
+        let mut credentials_file = None;
+        if !auth::is_authenticated(&instance_config.data_dir) {
+            if let Some(name) = &name {
+                info!("Instance {} needs an account to log in", name);
+            }
+            let written = auth::credentials(
+                &instance_config.credentials_file(),
+                matches.is_present("credentials-stdin"),
+            )
+            .and_then(|credentials| {
+                auth::CommandLineFile::write(&instance_config.data_dir, credentials)
+            });
+            match written {
+                Ok(file) => credentials_file = Some(file),
+                Err(e) => {
+                    error!("Failed to get the account's credentials: {}", e);
+                    return 1;
+                }
+            }
+        }
+        prepared.push((
+            name,
+            instance_config,
+            credentials_file,
+            Completions::default(),
+        ));
     }
 
-    // at this point the launcher should be installed, so we can create a server instance and start reading from it
-    let mut launch_args = vec![];
-    if let Some(email) = credentials.0 {
-        launch_args.push(format!("-User={}", email));
-    }
-    if let Some(password) = credentials.1 {
-        launch_args.push(format!("-Password={}", password));
+    // the operator console, which can complete the names of each instance's players and plugins
+    let completions = prepared
+        .iter()
+        .map(|(name, _, _, completions)| (name.clone(), completions.clone()))
+        .collect();
+    let mut console_receiver =
+        console::spawn(completions, config.data_dir.join("console_history.txt"));
+
+    // each game server runs in its own task, and gets the console lines addressed to it
+    let (exit_sender, mut exit_receiver) = mpsc::unbounded_channel::<(Option<String>, i32)>();
+    let mut consoles = BTreeMap::new();
+    for (name, instance_config, credentials_file, completions) in prepared {
+        let (console_sender, console_receiver) = mpsc::unbounded_channel();
+        consoles.insert(name.clone(), console_sender);
+
+        let game_command = game_command.clone();
+        let exit_sender = exit_sender.clone();
+        let handle = tokio::spawn(instance::scope(
+            name.clone(),
+            instance::run(
+                instance_config,
+                game_command,
+                credentials_file,
+                completions,
+                console_receiver,
+            ),
+        ));
+        // an instance that panicked has still exited, so the wrapper doesn't wait on it forever
+        tokio::spawn(instance::scope(name.clone(), async move {
+            let code = handle.await.unwrap_or_else(|e| {
+                error!("The game server's task failed: {}", e);
+                1
+            });
+            let _ = exit_sender.send((name, code));
+        }));
     }
 
-    let is_server_verbose = matches.is_present("server-verbose");
-    let mut server = Server::start(&launch_args, stdin_receiver).unwrap();
+    let mut selected = consoles.keys().next().cloned().flatten();
+    let mut code = shutdown::EXIT_CLEAN;
+    let mut stopping = false;
+    let shutdown_signal = shutdown::signal();
+    tokio::pin!(shutdown_signal);
 
-    info!("Server active");
-
-    let stdout = server.child.stdout.take().unwrap();
-    let reader = io::BufReader::new(stdout);
-    let mut lines = reader.lines();
-
-    let log_matcher =
-        Regex::new("^\\[[\\d\\.\\-:]+\\]\\[\\s*(?P<index>\\d+)\\](?P<body>.+)$").unwrap();
-
-    // a stream to handle sending rpc messages to plugins
-    let (plugin_rpc_sender, mut plugin_rpc_receiver) = mpsc::unbounded_channel::<rpc::Message>();
-
-    let grouped_regex_matchers: Vec<Arc<dyn GroupedRegexMatcher + Send>> = vec![
-        Arc::new(ChatRegexMatcher(plugin_rpc_sender.clone())),
-        Arc::new(ConnectRegexMatcher(plugin_rpc_sender.clone())),
-    ];
-    let mut grouped_regex_instances: Vec<GroupedRegexMatches<'_>> = vec![];
-
-    // repeatedly listen to stdout for new content
-    loop {
+    // the wrapper exits once every game server has stopped
+    while !consoles.is_empty() {
         tokio::select! {
-            Ok(Some(line)) = lines.next_line() => {
-                // line from the game server
-
-                if is_server_verbose {
-                    debug!(":: {}", line);
-                }
-
-                let log_match = match log_matcher.captures(line.as_str()) {
-                    Some(x) => x,
-                    None => continue
-                };
-
-                let index: i32 = (&log_match["index"]).parse().unwrap();
-                let body = &log_match["body"];
-
-                // handle each grouped regex instance, and break if one is matched
-                let mut i: usize = 0;
-                for instance in grouped_regex_instances.iter_mut() {
-                    let index_matches = match instance.index {
-                        Some(n) => index == n,
-                        None => true
-                    };
-
-                    if index_matches {
-                        let regexes = instance.matcher.regexes();
-                        let next_regex = &regexes[instance.captures.len()];
-
-                        let capture_names = next_regex.capture_names();
-                        if let Some(captures) = next_regex.captures(body) {
-                            // update last
-                            instance.last = Instant::now();
-
-                            // clone out captures into a map for ownership
-                            let mut map = HashMap::new();
-
-                            for group_name in capture_names {
-                                let group_name = match group_name {
-                                    Some(x) => x,
-                                    None => continue
-                                };
-                                let m = captures.name(group_name).unwrap().as_str().clone();
-                                map.insert(String::from(group_name), String::from(m));
-                            }
-
-                            // we have our map, update the instance
-                            instance.captures.push(map);
-
-                            // if our captures count is >= the regex count, our job here is done:
-                            // submit the rpc message
-                            if instance.captures.len() >= regexes.len() {
-                                instance.matcher.complete(&instance).await;
-                                break;
-                            }
-                        }
-                    }
-
-                    i += 1;
+            _ = &mut shutdown_signal, if !stopping => {
+                info!("Received a shutdown signal");
+                stopping = true;
+                for console in consoles.values() {
+                    let _ = console.send(".stop".into());
                 }
-
-                // loop terminated early somewhere, so we remove it at its index
-                if i < grouped_regex_instances.len() {
-                    grouped_regex_instances.remove(i);
-                    continue;
+            }
+            Some((name, instance_code)) = exit_receiver.recv() => {
+                consoles.remove(&name);
+                if let Some(name) = name {
+                    info!("Instance {} stopped", name);
                 }
+                code = code.max(instance_code);
+            }
+            Some(line) = console_receiver.recv() => {
+                route(&line, &consoles, &mut selected);
+            }
+        }
+    }
 
-                // handle each grouped regex matcher, trying to start new instances if possible
-                for matcher in grouped_regex_matchers.iter() {
-                    let matcher_regexes = matcher.regexes();
-                    let first_regex = &matcher_regexes[0];
-
-                    let capture_names = first_regex.capture_names();
-                    if let Some(captures) = first_regex.captures(body) {
-                        // effectively clone out captures into a map for ownership
-                        let mut map = HashMap::new();
-
-                        for group_name in capture_names {
-                            let group_name = match group_name {
-                                Some(x) => x,
-                                None => continue
-                            };
-                            let m = captures.name(group_name).unwrap().as_str().clone();
-                            map.insert(String::from(group_name), String::from(m));
-                        }
-
-                        // we match with the first regex, so let's start making a new instance
-                        let instance = GroupedRegexMatches { index: Some(index), matcher: matcher.clone(), captures: RegexCaptures::new(vec![map]), last: Instant::now(), timeout: Duration::from_secs(1) };
+    code
+}
 
-                        // if the grouped regex actually only has one regex, we can early-terminate and avoid adding it to the array
-                        if matcher_regexes.len() == 1 {
-                            matcher.complete(&instance).await;
-                            break;
-                        } else {
-                            // add it to the instances array
-                            grouped_regex_instances.push(instance);
-                        }
-                    }
-                }
+/// Sends a console line to the game server it's addressed to with `@<name>`, or to the selected
+/// one. `.stop` without an address stops them all, and `.instances` and `.use` list and select
+/// the game servers.
+fn route(
+    line: &str,
+    consoles: &BTreeMap<Option<String>, mpsc::UnboundedSender<String>>,
+    selected: &mut Option<String>,
+) {
+    let line = line.trim();
+    let addressed = line.starts_with('@');
+    let (target, line) = match line.strip_prefix('@') {
+        Some(addressed) => {
+            let (name, rest) = addressed
+                .split_once(char::is_whitespace)
+                .unwrap_or((addressed, ""));
+            (Some(name.to_owned()), rest.trim())
+        }
+        None => (selected.clone(), line),
+    };
 
-                // clean up expired regex matchers if their last instant exceeds some timeout
-                grouped_regex_instances.retain(|instance| instance.last + instance.timeout < Instant::now());
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some(".stop"), _) if !addressed => {
+            for console in consoles.values() {
+                let _ = console.send(line.into());
             }
-            Some(rpc_message) = plugin_rpc_receiver.recv() => {
-                // message from plugin rpc receiver
-
-                for instance in instances.iter() {
-                    instance.stdin.send(serde_json::to_string(&rpc_message).unwrap()).unwrap();
-                }
+            return;
+        }
+        (Some(".instances"), _) => {
+            info!("{} instances running", consoles.len());
+            for name in consoles.keys().flatten() {
+                let marker = if Some(name) == selected.as_ref() {
+                    " (selected)"
+                } else {
+                    ""
+                };
+                info!("  {}{}", name, marker);
             }
-            Some(matcher_instance) = new_matcher_receiver.recv() => {
-                // matcher from any plugin's new matcher async fn
-
-                grouped_regex_instances.push(matcher_instance);
+            return;
+        }
+        (Some(".use"), Some(name)) => {
+            if consoles.contains_key(&Some(name.to_owned())) {
+                *selected = Some(name.to_owned());
+                info!("Console lines now go to instance {}", name);
+            } else {
+                warn!("No instance {} is running", name);
             }
+            return;
+        }
+        (Some(".help"), _) if consoles.len() > 1 => {
+            info!("Several game servers are running. Lines go to the selected one, except:");
+            info!("  @<instance> <line> send a line to another instance");
+            info!("  .instances         list the running instances");
+            info!("  .use <instance>    select the instance lines go to");
+        }
+        _ => (),
+    }
+
+    match consoles.get(&target) {
+        Some(console) => {
+            let _ = console.send(line.into());
         }
+        None => warn!(
+            "No instance {} is running",
+            target.as_deref().unwrap_or_default()
+        ),
     }
 }
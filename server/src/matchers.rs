@@ -2,13 +2,18 @@ use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
 use plugin::{payloads::*, player::Player, rpc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use tokio::{sync::mpsc, time::Instant};
 use uuid::Uuid;
 
+use crate::{
+    bans::BanList,
+    players::{self, PlayerList},
+};
+
 lazy_static! {
     static ref CHAT_REGEX: Vec<Regex> =
         vec![Regex::new("LogChat: (?P<user>[^:]+): (?P<message>.*)$").unwrap()];
@@ -18,6 +23,10 @@ lazy_static! {
         Regex::new("^LogServerList: UserId: (?P<id>.+)$").unwrap(),
         Regex::new("^LogServerList: HandleId: (?P<handle>.+)$").unwrap()
     ];
+    static ref LEAVE_REGEX: Vec<Regex> = vec![Regex::new(
+        "^LogNet: UNetConnection::Close: .*UniqueId: BRICKADIA:(?P<id>[0-9a-fA-F\\-]+)"
+    )
+    .unwrap()];
 }
 
 /// A wrapper around the captures of a regex.
@@ -33,7 +42,7 @@ impl RegexCaptures {
 
     pub fn at(&self, ind: usize, key: &str) -> Option<&str> {
         match self.vec.get(ind) {
-            Some(map) => map.get(key.into()).map(String::as_str),
+            Some(map) => map.get(key).map(String::as_str),
             None => None,
         }
     }
@@ -76,27 +85,14 @@ impl fmt::Debug for GroupedRegexMatches<'_> {
     }
 }
 
-/// Runtime plugin regex.
-pub struct PluginRegexMatcher {
-    pub regexes: Vec<Regex>,
-    pub capture_sender: mpsc::Sender<RegexCaptures>,
+/// Player join regex. Banned players are kicked instead of being announced to plugins.
+pub struct ConnectRegexMatcher {
+    pub rpc: mpsc::UnboundedSender<rpc::Message>,
+    pub game_stdin: mpsc::UnboundedSender<String>,
+    pub players: PlayerList,
+    pub bans: BanList,
 }
 
-#[async_trait]
-impl GroupedRegexMatcher for PluginRegexMatcher {
-    fn regexes(&self) -> &Vec<Regex> {
-        &self.regexes
-    }
-
-    async fn complete(&self, instance: &GroupedRegexMatches<'_>) {
-        let captures = instance.captures.clone();
-        self.capture_sender.send(captures).await.unwrap();
-    }
-}
-
-/// Player join regex.
-pub struct ConnectRegexMatcher(pub mpsc::UnboundedSender<rpc::Message>);
-
 #[async_trait]
 impl GroupedRegexMatcher for ConnectRegexMatcher {
     fn regexes(&self) -> &'static Vec<Regex> {
@@ -110,9 +106,44 @@ impl GroupedRegexMatcher for ConnectRegexMatcher {
             name: name.into(),
             uuid,
         };
+
+        if let Some(ban) = self.bans.find(&uuid) {
+            warn!("Kicking banned player {} ({})", name, ban.reason);
+            self.game_stdin
+                .send(players::kick_command(name, &ban.reason))
+                .unwrap();
+            return;
+        }
+
+        self.players.join(player.clone());
         let message =
             rpc::Message::notification("connect", Some(serde_json::to_value(player).unwrap()));
-        self.0.send(message).unwrap();
+        self.rpc.send(message).unwrap();
+    }
+}
+
+/// Player leave regex.
+pub struct LeaveRegexMatcher(pub mpsc::UnboundedSender<rpc::Message>, pub PlayerList);
+
+#[async_trait]
+impl GroupedRegexMatcher for LeaveRegexMatcher {
+    fn regexes(&self) -> &'static Vec<Regex> {
+        &LEAVE_REGEX
+    }
+
+    async fn complete(&self, instance: &GroupedRegexMatches<'_>) {
+        let uuid: Uuid = match instance.captures.at(0, "id").unwrap().parse() {
+            Ok(uuid) => uuid,
+            Err(_) => return,
+        };
+
+        // connections close for more than just players, so only notify about players we know of
+        if let Some(player) = self.1.leave(&uuid) {
+            info!("{} left the game", player.name);
+            let message =
+                rpc::Message::notification("leave", Some(serde_json::to_value(player).unwrap()));
+            self.0.send(message).unwrap();
+        }
     }
 }
 
@@ -0,0 +1,162 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::Path};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// The name of the file operators put their overrides in, inside the plugin's data folder.
+pub const OVERRIDES_FILE: &str = "config.toml";
+
+/// The type of a config option.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Array,
+}
+
+impl OptionType {
+    fn name(&self) -> &'static str {
+        match self {
+            OptionType::String => "a string",
+            OptionType::Integer => "an integer",
+            OptionType::Float => "a number",
+            OptionType::Boolean => "a boolean",
+            OptionType::Array => "an array",
+        }
+    }
+
+    /// Checks a value against this type, converting integers given for floats.
+    fn check(&self, value: toml::Value) -> Option<toml::Value> {
+        match (self, value) {
+            (OptionType::String, value @ toml::Value::String(_))
+            | (OptionType::Integer, value @ toml::Value::Integer(_))
+            | (OptionType::Float, value @ toml::Value::Float(_))
+            | (OptionType::Boolean, value @ toml::Value::Boolean(_))
+            | (OptionType::Array, value @ toml::Value::Array(_)) => Some(value),
+            (OptionType::Float, toml::Value::Integer(n)) => Some(toml::Value::Float(n as f64)),
+            _ => None,
+        }
+    }
+}
+
+/// An option a plugin declares in the `[config]` section of its plugin.toml, e.g.
+///
+/// ```toml
+/// [config.greeting]
+/// type = "string"
+/// default = "Hello!"
+/// description = "What to greet players with"
+/// ```
+#[derive(Deserialize, Debug)]
+pub struct ConfigOption {
+    #[serde(rename = "type")]
+    pub kind: OptionType,
+    /// The value used when the operator doesn't override it. Options without one must be overridden.
+    pub default: Option<toml::Value>,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Checks that each option's default, if it has one, is of the option's type.
+pub fn check_defaults(schema: &BTreeMap<String, ConfigOption>) -> Vec<String> {
+    schema
+        .iter()
+        .filter(|(_, option)| match &option.default {
+            Some(default) => option.kind.check(default.clone()).is_none(),
+            None => false,
+        })
+        .map(|(key, option)| {
+            format!(
+                "the default for config.{} should be {}",
+                key,
+                option.kind.name()
+            )
+        })
+        .collect()
+}
+
+/// A config file with every option commented out, described and set to its default.
+fn template(schema: &BTreeMap<String, ConfigOption>) -> String {
+    let mut template = String::from("# Uncomment an option to override its default.\n");
+    for (key, option) in schema.iter() {
+        template.push('\n');
+        if !option.description.is_empty() {
+            template.push_str(&format!("# {}\n", option.description));
+        }
+        match &option.default {
+            Some(value) => template.push_str(&format!("# {} = {}\n", key, value)),
+            None => template.push_str(&format!("# {} = ({}, required)\n", key, option.kind.name())),
+        }
+    }
+    template
+}
+
+/// Merges the operator's overrides in `data_dir` over the defaults in `schema`.
+///
+/// Every problem is reported at once: overrides for options that don't exist, values of the
+/// wrong type and options left without a value.
+pub fn load(schema: &BTreeMap<String, ConfigOption>, data_dir: &Path) -> Result<Value> {
+    let path = data_dir.join(OVERRIDES_FILE);
+    let mut overrides = match fs::read_to_string(&path) {
+        Ok(contents) => match toml::from_str::<toml::value::Table>(&contents) {
+            Ok(overrides) => overrides,
+            Err(e) => bail!("invalid config in {}: {}", path.display(), e),
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            // give the operator something to start from
+            if !schema.is_empty() {
+                let _ = fs::create_dir_all(data_dir);
+                let _ = fs::write(&path, template(schema));
+            }
+            toml::value::Table::new()
+        }
+        Err(e) => bail!("failed to read {}: {}", path.display(), e),
+    };
+
+    let mut problems = vec![];
+    for key in overrides.keys() {
+        if !schema.contains_key(key) {
+            problems.push(format!("unknown option {}", key));
+        }
+    }
+
+    let mut config = Map::new();
+    for (key, option) in schema.iter() {
+        let (value, source) = match overrides.remove(key) {
+            Some(value) => (value, "override"),
+            None => match &option.default {
+                Some(value) => (value.clone(), "default"),
+                None => {
+                    problems.push(format!("{} has no value", key));
+                    continue;
+                }
+            },
+        };
+
+        match option.kind.check(value) {
+            Some(value) => {
+                config.insert(key.clone(), serde_json::to_value(value)?);
+            }
+            None => problems.push(format!(
+                "{} should be {} (from the {})",
+                key,
+                option.kind.name(),
+                source
+            )),
+        }
+    }
+
+    if !problems.is_empty() {
+        bail!(
+            "invalid config in {}: {}",
+            path.display(),
+            problems.join(", ")
+        );
+    }
+
+    Ok(Value::Object(config))
+}
@@ -0,0 +1,110 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use plugin::{rpc::RpcError, Capability};
+use serde::{Deserialize, Serialize};
+
+use crate::files;
+
+/// The file, inside a plugin's data folder, listing the capabilities the operator approved.
+pub const APPROVALS_FILE: &str = "approved.toml";
+
+#[derive(Serialize, Deserialize, Default)]
+struct Approvals {
+    #[serde(default)]
+    capabilities: Vec<Capability>,
+}
+
+/// The capabilities the operator approved for the plugin whose data is in `data_dir`.
+pub fn approved(data_dir: &Path) -> Result<Vec<Capability>> {
+    let path = data_dir.join(APPROVALS_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(toml::from_str::<Approvals>(&contents)?.capabilities),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Approves capabilities for a plugin, on top of those it already had.
+pub fn approve(data_dir: &Path, capabilities: &[Capability]) -> Result<()> {
+    let mut approvals = Approvals {
+        capabilities: approved(data_dir)?,
+    };
+    approvals.capabilities.extend_from_slice(capabilities);
+    approvals.capabilities.sort();
+    approvals.capabilities.dedup();
+
+    files::atomic_write(
+        &data_dir.join(APPROVALS_FILE),
+        toml::to_string(&approvals)?.as_bytes(),
+    )
+}
+
+/// What a running plugin is allowed to do: the capabilities it declared and the operator approved.
+#[derive(Debug)]
+pub struct Permissions {
+    declared: Vec<Capability>,
+    approved: Vec<Capability>,
+}
+
+impl Permissions {
+    pub fn load(declared: &[Capability], data_dir: &Path) -> Result<Self> {
+        Ok(Permissions {
+            declared: declared.to_vec(),
+            approved: approved(data_dir)?,
+        })
+    }
+
+    /// The capabilities the plugin can use.
+    pub fn granted(&self) -> Vec<Capability> {
+        self.declared
+            .iter()
+            .filter(|c| self.approved.contains(c))
+            .copied()
+            .collect()
+    }
+
+    /// The capabilities the plugin declared but the operator hasn't approved yet.
+    pub fn pending(&self) -> Vec<Capability> {
+        self.declared
+            .iter()
+            .filter(|c| !self.approved.contains(c))
+            .copied()
+            .collect()
+    }
+
+    /// Checks that the plugin may send a message with `method`.
+    pub fn check(&self, method: &str) -> Result<(), RpcError> {
+        let capability = match Capability::required_for(method) {
+            Some(capability) => capability,
+            None => return Ok(()),
+        };
+
+        let problem = if !self.declared.contains(&capability) {
+            "which the plugin doesn't declare in its plugin.toml"
+        } else if !self.approved.contains(&capability) {
+            "which the operator hasn't approved"
+        } else {
+            return Ok(());
+        };
+
+        Err(RpcError::new(
+            RpcError::PERMISSION_DENIED,
+            format!(
+                "{} needs the {} capability, {}",
+                method,
+                capability.name(),
+                problem
+            ),
+        ))
+    }
+}
+
+/// Lists capabilities the way they're written in plugin.toml.
+pub fn names(capabilities: &[Capability]) -> String {
+    capabilities
+        .iter()
+        .map(|c| c.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
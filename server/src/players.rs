@@ -0,0 +1,55 @@
+use std::sync::{Arc, RwLock};
+
+use plugin::player::Player;
+use uuid::Uuid;
+
+/// The console command kicking a player, with quotes stripped from the reason so it stays one argument.
+pub fn kick_command(name: &str, reason: &str) -> String {
+    let reason: String = reason
+        .chars()
+        .map(|c| if c == '"' || c.is_control() { '\'' } else { c })
+        .collect();
+    format!("Chat.Command /Kick \"{}\" \"{}\"", name, reason)
+}
+
+/// The players the server knows to be online, shared between the game reader and plugins.
+#[derive(Clone, Default)]
+pub struct PlayerList(Arc<RwLock<Vec<Player>>>);
+
+impl PlayerList {
+    pub fn join(&self, player: Player) {
+        let mut players = self.0.write().unwrap();
+        players.retain(|p| p.uuid != player.uuid);
+        players.push(player);
+    }
+
+    pub fn leave(&self, uuid: &Uuid) -> Option<Player> {
+        let mut players = self.0.write().unwrap();
+        let index = players.iter().position(|p| &p.uuid == uuid)?;
+        Some(players.remove(index))
+    }
+
+    /// Finds an online player by UUID, exact name, or case-insensitive name, in that order.
+    pub fn find(&self, target: &str) -> Option<Player> {
+        let players = self.0.read().unwrap();
+
+        if let Ok(uuid) = target.parse::<Uuid>() {
+            return players.iter().find(|p| p.uuid == uuid).cloned();
+        }
+
+        players
+            .iter()
+            .find(|p| p.name == target)
+            .or_else(|| players.iter().find(|p| p.name.eq_ignore_ascii_case(target)))
+            .cloned()
+    }
+
+    /// Forgets every player, e.g. when the game restarts.
+    pub fn clear(&self) {
+        self.0.write().unwrap().clear();
+    }
+
+    pub fn all(&self) -> Vec<Player> {
+        self.0.read().unwrap().clone()
+    }
+}
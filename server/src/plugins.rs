@@ -1,10 +1,23 @@
-use std::{convert::TryInto, path::PathBuf, process::Stdio, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    convert::TryInto,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 
-use log::{debug, error, info, trace, warn};
-use plugin::{logging::LogSeverity, payloads, rpc, Plugin};
-use regex::Regex;
+use log::{debug, error, info, log, warn, Level};
+use plugin::{
+    payloads::{self, PluginMessagePayload, PluginStatusPayload},
+    rpc::{self, RpcError},
+    Plugin, RestartPolicy,
+};
 use serde::Deserialize;
 use serde_json::Value;
 use tokio::{
@@ -15,17 +28,41 @@ use tokio::{
         mpsc::{self, UnboundedSender},
         Mutex,
     },
-    time::Instant,
+    task,
+    time::{sleep, timeout_at, Instant},
+};
+
+use crate::{
+    bans::{self, Ban, BanList},
+    build::{self, SourceFiles},
+    check, config, instance,
+    logs::{self, PluginLog},
+    options::{self, ConfigOption},
+    permissions::{self, Permissions},
+    players::{self, PlayerList},
+    schedule::{Schedule, Schedules},
+    server::CrashTracker,
+    shutdown,
+    store::Store,
 };
 
-use crate::matchers::{GroupedRegexMatches, PluginRegexMatcher, RegexCaptures};
+/// How often the plugin watcher checks for changed files.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// How often a plugin that closed its stdout is checked for having exited.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Represents the configuration of the plugin.
 #[derive(Deserialize)]
 pub struct PluginConfig {
     plugin: Plugin,
+    /// The options the plugin can be configured with, by name.
+    #[serde(default)]
+    config: BTreeMap<String, ConfigOption>,
     #[serde(skip)]
     path: Option<PathBuf>,
+    /// The data folder of the game server the plugin runs for.
+    #[serde(skip)]
+    instance_data_dir: PathBuf,
 }
 
 impl PluginConfig {
@@ -36,13 +73,106 @@ impl PluginConfig {
     pub fn path(&self) -> &Option<PathBuf> {
         &self.path
     }
+
+    /// The plugin's executable, which is its target inside its folder.
+    pub fn target_path(&self) -> Option<PathBuf> {
+        self.path
+            .as_ref()
+            .map(|path| path.join(self.plugin.target()))
+    }
+
+    /// What the plugin runs: its command if it has one, otherwise its target.
+    pub fn program(&self) -> Option<PathBuf> {
+        let path = self.path.as_ref()?;
+        match self.plugin.command() {
+            // a bare name like `node` is found on the PATH
+            Some(command) if Path::new(command).components().count() == 1 => Some(command.into()),
+            Some(command) => Some(path.join(command)),
+            None => self.target_path(),
+        }
+    }
+
+    /// The folder the plugin runs in, which is its own folder unless it says otherwise.
+    pub fn cwd(&self) -> Option<PathBuf> {
+        let path = self.path.as_ref()?;
+        Some(match self.plugin.cwd() {
+            Some(cwd) => path.join(cwd),
+            None => path.clone(),
+        })
+    }
+
+    pub fn options(&self) -> &BTreeMap<String, ConfigOption> {
+        &self.config
+    }
+
+    /// The plugin's identifier, which is the name of its folder.
+    pub fn id(&self) -> String {
+        self.path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.plugin.name().into())
+    }
+
+    /// Where the plugin's data lives, including the operator's overrides of its config.
+    pub fn data_dir(&self) -> PathBuf {
+        self.instance_data_dir.join("plugins").join(self.id())
+    }
+
+    /// The data folder of the game server the plugin runs for.
+    pub fn instance_data_dir(&self) -> &Path {
+        &self.instance_data_dir
+    }
 }
 
-/// A group of channels each plugin should have access to.
+/// The channels and shared state each plugin should have access to.
 #[derive(Clone)]
-pub struct PluginChannels<'a> {
+pub struct PluginContext {
+    /// Where the game server's plugins are installed.
+    pub plugins_dir: PathBuf,
+    /// The game server's data folder.
+    pub data_dir: PathBuf,
     pub stdin: mpsc::UnboundedSender<String>,
-    pub matchers: mpsc::UnboundedSender<GroupedRegexMatches<'a>>,
+    pub players: PlayerList,
+    pub bans: BanList,
+    pub events: mpsc::UnboundedSender<PluginEvent>,
+}
+
+/// Something that happened to a plugin, for the wrapper to handle.
+pub enum PluginEvent {
+    /// The plugin's process exited without being asked to.
+    Exited {
+        id: String,
+        status: Option<ExitStatus>,
+    },
+    /// A plugin, or every plugin if there's no id, should be reloaded.
+    Reload { id: Option<String>, reason: String },
+    /// A plugin sent a message to another plugin.
+    Message {
+        from: String,
+        target: String,
+        message: Value,
+    },
+    /// The plugins being reloaded were built, or failed to build.
+    Built {
+        built: Vec<PluginConfig>,
+        failed: Vec<(String, anyhow::Error)>,
+    },
+    /// The plugins being reloaded have stopped, so they can be started again.
+    Stopped { stopped: Vec<PluginInstance> },
+}
+
+/// A reload that's underway.
+struct Reload {
+    /// The plugin being reloaded, or None if every plugin is.
+    id: Option<String>,
+    /// What to start once the old instances have stopped, in load order.
+    start: Vec<Arc<PluginConfig>>,
+    /// Plugins that were running but are no longer in the plugins folder.
+    removed: HashSet<String>,
+    /// What was sent to each plugin being restarted while it was down, by id, to be delivered
+    /// once it's running again.
+    held: HashMap<String, Vec<String>>,
 }
 
 /// Represents an instance of the plugin running.
@@ -50,150 +180,1122 @@ pub struct PluginInstance {
     pub config: Arc<PluginConfig>,
     pub process: Arc<Mutex<Child>>,
     pub stdin: mpsc::UnboundedSender<String>,
+    pub permissions: Arc<Permissions>,
+    pub schedules: Schedules,
+    /// Set once the wrapper asks the plugin to stop, so its exit isn't treated as unexpected.
+    stopping: Arc<AtomicBool>,
 }
 
 impl PluginInstance {
-    pub fn start(config: PluginConfig, channels: &PluginChannels<'_>) -> Result<PluginInstance> {
+    pub fn start(config: Arc<PluginConfig>, context: &PluginContext) -> Result<PluginInstance> {
         if config.path.is_none() {
             bail!("no plugin path found");
         }
 
-        // the path should be the target path
-        let mut path = config.path().to_owned().unwrap();
-        path.push(config.plugin.target());
+        // a plugin with a bad config isn't started at all, rather than failing once it's running
+        let options = options::load(&config.config, &config.data_dir())?;
+
+        let store = Store::load(&config.data_dir())
+            .map_err(|e| anyhow!("failed to load the plugin's store: {}", e))?;
+
+        let permissions = Permissions::load(config.plugin.capabilities(), &config.data_dir())
+            .map_err(|e| anyhow!("failed to load the plugin's approved capabilities: {}", e))?;
+        let pending = permissions.pending();
+        if !pending.is_empty() {
+            warn!(
+                "Plugin {} needs capabilities that haven't been approved: {}. Approve them with .approve {}",
+                config.id(),
+                permissions::names(&pending),
+                config.id()
+            );
+        }
+        let permissions = Arc::new(permissions);
+
+        // the program is made absolute, as relative paths are ambiguous once the cwd changes
+        let mut program = config.program().unwrap();
+        if program.components().count() > 1 {
+            program = std::fs::canonicalize(&program)
+                .map_err(|e| anyhow!("failed to find {}: {}", program.display(), e))?;
+        }
+        let cwd = config.cwd().unwrap();
+        if !cwd.is_dir() {
+            bail!("the plugin's cwd {} isn't a folder", cwd.display());
+        }
+
+        // the config notification goes first, ahead of any schedule that's already due
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+        let notification = rpc::Message::notification("config", Some(options.clone()));
+        sender
+            .send(serde_json::to_string(&notification).unwrap())
+            .unwrap();
+        let schedules = Schedules::load(&config.data_dir(), sender.clone())
+            .map_err(|e| anyhow!("failed to load the plugin's schedules: {}", e))?;
 
-        let mut child = Command::new(path)
+        let mut child = Command::new(&program)
+            .args(config.plugin.args())
+            .envs(config.plugin.env())
+            .envs(instance::current().map(|name| ("BRIXIDE_INSTANCE", name.to_string())))
+            .current_dir(&cwd)
+            .kill_on_drop(true)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()?;
+            .spawn()
+            .map_err(|e| anyhow!("failed to run {}: {}", program.display(), e))?;
 
         let mut child_stdin = child.stdin.take().unwrap(); // this will be moved into the task that listens for stdin
         let child_stdout = child.stdout.take().unwrap(); // this will be moved into the task handling the plugin
+        let child_stderr = child.stderr.take().unwrap(); // drained so a chatty plugin doesn't block on a full pipe
 
         // sending to stdin task
-        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
-        tokio::spawn(async move {
+        instance::spawn(async move {
             while let Some(mut x) = receiver.recv().await {
                 x.push('\n');
-                match child_stdin.write_all(&x[..].as_bytes()).await {
-                    Ok(_) => (),
-                    Err(_) => break,
+                if child_stdin.write_all(x.as_bytes()).await.is_err() {
+                    break;
                 }
             }
         });
 
-        let config_arc = Arc::new(config);
+        // reading stderr task, which ends when the plugin exits
+        let plugin_log = PluginLog::new(&config.instance_data_dir, &config.id());
+        let stderr_log = plugin_log.clone();
+        let name = config.plugin.name().to_owned();
+        instance::spawn(async move {
+            let mut lines = io::BufReader::new(child_stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let level = logs::stderr_level(&line);
+                log!(level, "[{}] {}", name, line);
+                stderr_log.write(level, &line);
+            }
+        });
+
+        let config_arc = config;
         let child_mtx = Arc::new(Mutex::new(child));
+        let stopping = Arc::new(AtomicBool::new(false));
 
         // reading stdout task
         let config_thread_arc = config_arc.clone();
-        let _child_thread_mtx = child_mtx.clone(); // is this necessary?
+        let child_thread_mtx = child_mtx.clone();
+        let stopping_thread = stopping.clone();
+        let events = context.events.clone();
+        let thread_permissions = permissions.clone();
+        let thread_schedules = schedules.clone();
 
-        let game_stdin = channels.stdin.clone();
-        let _regex_matchers = channels.matchers.clone();
-        tokio::spawn(async move {
+        let plugin_stdin = sender.clone();
+        let game_stdin = context.stdin.clone();
+        let players = context.players.clone();
+        let bans = context.bans.clone();
+        instance::spawn(async move {
             let reader = io::BufReader::new(child_stdout);
             let mut lines = reader.lines();
 
-            async fn match_regex(
-                matchers_channel: UnboundedSender<GroupedRegexMatches<'_>>,
-                regexes: Vec<Regex>,
-                timeout: Duration,
-            ) -> Option<RegexCaptures> {
-                let (sender, mut receiver) = mpsc::channel(1);
-                let matcher = PluginRegexMatcher {
-                    regexes,
-                    capture_sender: sender,
-                };
-                let matcher_arc = Arc::new(matcher);
-                let instance = GroupedRegexMatches {
-                    matcher: matcher_arc.clone(),
-                    index: None,
-                    captures: RegexCaptures::default(),
-                    last: Instant::now(),
-                    timeout,
+            loop {
+                let line = match lines.next_line().await {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!(
+                            "Failed to read from plugin {}: {}",
+                            config_thread_arc.id(),
+                            e
+                        );
+                        break;
+                    }
                 };
-                matchers_channel.send(instance).unwrap();
-
-                receiver.recv().await
-            }
-
-            // truth be told, if this thread panics, it doesn't really matter because the plugin died in some regard
-            // todo: handle this a little bit better
-            while let Some(line) = lines.next_line().await.unwrap() {
                 let rpc_message: rpc::Message = match serde_json::from_str(&line[..]) {
                     Ok(m) => m,
                     Err(_) => continue,
                 };
 
+                // refuse anything the plugin isn't allowed to do
+                if let Some(method) = rpc_message.method() {
+                    if let Err(error) = thread_permissions.check(method) {
+                        warn!(
+                            "Plugin {} was denied {}: {}",
+                            config_thread_arc.id(),
+                            method,
+                            error.message()
+                        );
+                        if let Some(id) = rpc_message.id().cloned() {
+                            reply(&plugin_stdin, id, Err(error));
+                        }
+                        continue;
+                    }
+                }
+
                 // handle rpc messages sent by the plugin
                 match rpc_message.method() {
                     Some("log") => {
                         // log messages
-                        let payload: payloads::LogPayload = rpc_message.try_into().unwrap();
-                        match payload.severity {
-                            LogSeverity::Debug => {
-                                debug!("[{}] {}", config_thread_arc.plugin.name(), payload.content)
-                            }
-                            LogSeverity::Info => {
-                                info!("[{}] {}", config_thread_arc.plugin.name(), payload.content)
-                            }
-                            LogSeverity::Warn => {
-                                warn!("[{}] {}", config_thread_arc.plugin.name(), payload.content)
-                            }
-                            LogSeverity::Error => {
-                                error!("[{}] {}", config_thread_arc.plugin.name(), payload.content)
-                            }
-                            LogSeverity::Trace => {
-                                trace!("[{}] {}", config_thread_arc.plugin.name(), payload.content)
-                            }
-                        }
+                        let payload: payloads::LogPayload = match rpc_message.try_into() {
+                            Ok(payload) => payload,
+                            Err(_) => continue,
+                        };
+                        let level: Level = payload.severity.into();
+                        log!(
+                            level,
+                            "[{}] {}",
+                            config_thread_arc.plugin.name(),
+                            payload.content
+                        );
+                        plugin_log.write(level, &payload.content);
                     }
                     Some("broadcast") => {
-                        // broadcast text
-                        if let rpc::Message::Notification { params, .. } = rpc_message {
-                            match params {
-                                Some(Value::String(str)) => {
-                                    game_stdin.send(format!("Chat.Broadcast {}", str)).unwrap();
-                                }
-                                _ => (),
+                        // broadcast text, one message per line so line breaks can't start new console commands
+                        if let rpc::Message::Notification {
+                            params: Some(Value::String(str)),
+                            ..
+                        } = rpc_message
+                        {
+                            for line in str.lines().filter(|line| !line.trim().is_empty()) {
+                                game_stdin.send(format!("Chat.Broadcast {}", line)).unwrap();
                             }
                         }
                     }
                     Some("writeln") => {
                         // write a line directly to the server stdin
-                        if let rpc::Message::Notification { params, .. } = rpc_message {
-                            match params {
-                                Some(Value::String(str)) => game_stdin.send(str).unwrap(),
-                                _ => (),
-                            }
+                        if let rpc::Message::Notification {
+                            params: Some(Value::String(str)),
+                            ..
+                        } = rpc_message
+                        {
+                            game_stdin.send(str).unwrap();
+                        }
+                    }
+                    Some("whisper") => {
+                        // privately message an online player
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            whisper(m, &players, &game_stdin)
+                        });
+                    }
+                    Some("players.kick") => {
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            kick(m, &players, &game_stdin)
+                        });
+                    }
+                    Some("players.ban") => {
+                        let issuer = config_thread_arc.id();
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            ban(m, &issuer, &players, &bans, &game_stdin)
+                        });
+                    }
+                    Some("players.unban") => {
+                        handle(&plugin_stdin, rpc_message, |m| unban(m, &bans));
+                    }
+                    Some("config.get") => {
+                        handle(&plugin_stdin, rpc_message, |_| Ok(options.clone()));
+                    }
+                    Some("store.get") => {
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::StoreKeyPayload = m.try_into()?;
+                            Ok(store.get(&payload.key).unwrap_or(Value::Null))
+                        });
+                    }
+                    Some("store.set") => {
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::StoreSetPayload = m.try_into()?;
+                            store
+                                .set(&payload.key, payload.value)
+                                .map(|()| Value::Null)
+                                .map_err(internal_error)
+                        });
+                    }
+                    Some("store.delete") => {
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::StoreKeyPayload = m.try_into()?;
+                            store
+                                .delete(&payload.key)
+                                .map(Value::Bool)
+                                .map_err(internal_error)
+                        });
+                    }
+                    Some("store.list") => {
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::StoreListPayload = m.try_into()?;
+                            Ok(store.list(&payload.prefix).into())
+                        });
+                    }
+                    Some("schedule.create") => {
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::ScheduleCreatePayload = m.try_into()?;
+                            let schedule = Schedule::new(payload).map_err(|e| {
+                                RpcError::new(RpcError::INVALID_PARAMS, e.to_string())
+                            })?;
+                            thread_schedules
+                                .create(schedule)
+                                .map(|()| Value::Null)
+                                .map_err(internal_error)
+                        });
+                    }
+                    Some("schedule.cancel") => {
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::ScheduleNamePayload = m.try_into()?;
+                            thread_schedules
+                                .cancel(&payload.name)
+                                .map(Value::Bool)
+                                .map_err(internal_error)
+                        });
+                    }
+                    Some("plugins.reload") => {
+                        // the manager does the reloading, once this plugin has its reply
+                        let requested_by = config_thread_arc.id();
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::ReloadPayload = m.try_into()?;
+                            let _ = events.send(PluginEvent::Reload {
+                                id: payload.id,
+                                reason: format!("requested by {}", requested_by),
+                            });
+                            Ok(Value::Null)
+                        });
+                    }
+                    Some("plugins.send") => {
+                        // the manager passes the message on to the target plugin
+                        let from = config_thread_arc.id();
+                        handle(&plugin_stdin, rpc_message, |m| {
+                            let payload: payloads::PluginSendPayload = m.try_into()?;
+                            let _ = events.send(PluginEvent::Message {
+                                from,
+                                target: payload.target,
+                                message: payload.message,
+                            });
+                            Ok(Value::Null)
+                        });
+                    }
+                    _ => {
+                        // let requests for unknown methods know they won't be answered
+                        if let rpc::Message::Request { id, method, .. } = rpc_message {
+                            let error = RpcError::new(
+                                RpcError::METHOD_NOT_FOUND,
+                                format!("unknown method {}", method),
+                            );
+                            reply(&plugin_stdin, id, Err(error));
                         }
                     }
-                    _ => (),
                 }
             }
+
+            thread_schedules.stop();
+
+            // stdout closed, which almost always means the plugin exited. it could still be running
+            // with its stdout closed, so poll rather than holding the process lock while waiting
+            let status = loop {
+                if stopping_thread.load(Ordering::SeqCst) {
+                    return;
+                }
+                match child_thread_mtx.lock().await.try_wait() {
+                    Ok(Some(status)) => break Some(status),
+                    Ok(None) => (),
+                    Err(_) => break None,
+                }
+                sleep(EXIT_POLL_INTERVAL).await;
+            };
+
+            let _ = events.send(PluginEvent::Exited {
+                id: config_thread_arc.id(),
+                status,
+            });
         });
 
         Ok(PluginInstance {
             config: config_arc,
             process: child_mtx,
             stdin: sender,
+            permissions,
+            schedules,
+            stopping,
         })
     }
+
+    /// Sends a message to the plugin.
+    pub fn send(&self, message: &rpc::Message) {
+        let _ = self.stdin.send(serde_json::to_string(message).unwrap());
+    }
+
+    /// Sends the plugin a `shutdown` notification. Its exit from here on is expected.
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.send(&rpc::Message::notification("shutdown", None));
+    }
+
+    fn status(&self, exit_code: Option<i32>) -> PluginStatusPayload {
+        PluginStatusPayload {
+            id: self.config.id(),
+            name: self.config.plugin().name().into(),
+            exit_code,
+        }
+    }
+
+    /// Waits for the plugin's process to exit by itself, returning false if it is still running at the deadline.
+    pub async fn wait_until(&self, deadline: Instant) -> bool {
+        let mut process = self.process.lock().await;
+        match timeout_at(deadline, process.wait()).await {
+            Ok(Ok(status)) => {
+                debug!("Plugin {} exited ({})", self.config.id(), status);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Kills the plugin's process.
+    pub async fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let mut process = self.process.lock().await;
+        if let Err(e) = process.kill().await {
+            warn!("Failed to stop plugin {}: {}", self.config.id(), e);
+        }
+    }
 }
 
-/// Scan the plugins folder for plugins, and generate a list of them
-pub async fn scan() -> Vec<PluginConfig> {
-    let mut plugins = vec![];
+/// Starts the plugins and keeps them running according to their restart policies.
+pub struct PluginManager {
+    context: PluginContext,
+    instances: Vec<PluginInstance>,
+    crashes: HashMap<String, CrashTracker>,
+    /// Plugins waiting out their backoff before being restarted.
+    restarts: Vec<(Instant, Arc<PluginConfig>)>,
+    /// Plugin ids in the order they start, so they can be stopped in reverse.
+    order: Vec<String>,
+    reloading: Option<Reload>,
+    queued_reloads: VecDeque<Option<String>>,
+}
 
-    let paths = fs::read_dir("plugins").await;
-    if let Err(_) = paths {
-        warn!("Plugins folder doesn't exist, couldn't find any plugins");
-        return vec![];
+impl PluginManager {
+    pub fn new(context: PluginContext) -> Self {
+        PluginManager {
+            context,
+            instances: vec![],
+            crashes: HashMap::new(),
+            restarts: vec![],
+            order: vec![],
+            reloading: None,
+            queued_reloads: VecDeque::new(),
+        }
     }
 
-    let mut paths = paths.unwrap();
+    pub fn instances(&self) -> &[PluginInstance] {
+        &self.instances
+    }
+
+    /// The ids of the running plugins.
+    pub fn ids(&self) -> Vec<String> {
+        self.instances.iter().map(|i| i.config.id()).collect()
+    }
+
+    /// Starts plugins in dependency order, leaving out those whose dependencies are missing,
+    /// cyclic or failed to build or start.
+    pub async fn start_all(&mut self, configs: Vec<PluginConfig>) {
+        let configs = load_order(configs);
+        self.order = configs.iter().map(|c| c.id()).collect();
+
+        for config in configs {
+            let id = config.id();
+            if let Err(e) = build::build_if_needed(&config).await {
+                warn!("Plugin {} failed to build: {}", id, e);
+                continue;
+            }
+            if let Err(e) = self.start(Arc::new(config)) {
+                warn!("Plugin {} failed to start: {}", id, e);
+            }
+        }
+    }
+
+    /// Starts a plugin, telling the others that it started.
+    pub fn start(&mut self, config: Arc<PluginConfig>) -> Result<()> {
+        self.check_dependencies(&config)?;
+        let instance = PluginInstance::start(config, &self.context)?;
+        self.broadcast(&instance.status(None).notification("plugin.started"));
+        self.instances.push(instance);
+        self.sort();
+        Ok(())
+    }
+
+    /// Fails if a plugin the given one depends on isn't running.
+    fn check_dependencies(&self, config: &PluginConfig) -> Result<()> {
+        let running = self.ids();
+        match config
+            .plugin
+            .depends()
+            .iter()
+            .find(|d| !running.contains(d))
+        {
+            Some(dependency) => bail!("its dependency {} isn't running", dependency),
+            None => Ok(()),
+        }
+    }
+
+    /// Puts the running plugins back in load order, after one was started out of turn.
+    fn sort(&mut self) {
+        let order = &self.order;
+        self.instances.sort_by_key(|i| {
+            order
+                .iter()
+                .position(|id| id == &i.config.id())
+                .unwrap_or(usize::MAX)
+        });
+    }
+
+    /// Sends a message to every running plugin, and to those being restarted by a reload once
+    /// they're running again.
+    pub fn broadcast(&mut self, message: &rpc::Message) {
+        for instance in self.instances.iter() {
+            instance.send(message);
+        }
+        if let Some(reload) = &mut self.reloading {
+            let line = serde_json::to_string(message).unwrap();
+            for held in reload.held.values_mut() {
+                held.push(line.clone());
+            }
+        }
+    }
+
+    /// Approves every capability the plugin with the given id declares, then reloads it so they take effect.
+    pub async fn approve(&mut self, id: &str) -> Result<()> {
+        let config = match scan(&self.context.plugins_dir, &self.context.data_dir)
+            .await
+            .into_iter()
+            .find(|c| c.id() == id)
+        {
+            Some(config) => config,
+            None => bail!("no plugin {} in the plugins folder", id),
+        };
+
+        let capabilities = config.plugin.capabilities();
+        permissions::approve(&config.data_dir(), capabilities)?;
+        info!(
+            "Approved capabilities for plugin {}: {}",
+            id,
+            permissions::names(capabilities)
+        );
+
+        self.reload(Some(id.into()));
+        Ok(())
+    }
+
+    /// Rereads the plugins folder and restarts the plugin with the given id along with the plugins
+    /// that depend on it, or every plugin if there's no id. Plugins are built and stopped off the
+    /// main loop, and reloads happen one at a time, so one asked for during another waits its turn.
+    pub fn reload(&mut self, id: Option<String>) {
+        if self.reloading.is_some() {
+            self.queued_reloads.push_back(id);
+            return;
+        }
+
+        self.reloading = Some(Reload {
+            id: id.clone(),
+            start: vec![],
+            removed: HashSet::new(),
+            held: HashMap::new(),
+        });
+
+        // scanning and building happen in a task, which reports back with a Built event
+        let plugins_dir = self.context.plugins_dir.clone();
+        let data_dir = self.context.data_dir.clone();
+        let events = self.context.events.clone();
+        instance::spawn(async move {
+            let mut configs = scan(&plugins_dir, &data_dir).await;
+            if let Some(id) = &id {
+                configs.retain(|c| &c.id() == id);
+            }
+
+            let mut built = vec![];
+            let mut failed = vec![];
+            for config in configs {
+                match build::build_if_needed(&config).await {
+                    Ok(()) => built.push(config),
+                    Err(e) => failed.push((config.id(), e)),
+                }
+            }
+            let _ = events.send(PluginEvent::Built { built, failed });
+        });
+    }
+
+    /// Stops the plugins a reload replaces, once their new versions are built. A plugin that failed
+    /// to build keeps running as it was.
+    fn reload_built(&mut self, mut built: Vec<PluginConfig>, failed: Vec<(String, anyhow::Error)>) {
+        let mut reload = match self.reloading.take() {
+            Some(reload) => reload,
+            None => return,
+        };
+
+        for (id, e) in failed.iter() {
+            warn!("Failed to reload plugin {}: {}", id, e);
+        }
+        if let Some(id) = &reload.id {
+            if built.is_empty() {
+                if failed.is_empty() {
+                    warn!(
+                        "Failed to reload plugin {}: no plugin {} in the plugins folder",
+                        id, id
+                    );
+                }
+                self.finish_reload();
+                return;
+            }
+        }
+
+        let mut stopping: HashSet<String> = built.iter().map(|c| c.id()).collect();
+        if reload.id.is_none() {
+            // every plugin is reloaded, so the order is worked out again
+            built = load_order(built);
+            self.order = built.iter().map(|c| c.id()).collect();
+            reload.removed = self
+                .ids()
+                .into_iter()
+                .filter(|id| !stopping.contains(id) && !failed.iter().any(|(f, _)| f == id))
+                .collect();
+            stopping.extend(reload.removed.iter().cloned());
+        }
+        reload.start = built.into_iter().map(Arc::new).collect();
+
+        // plugins depending on a stopped plugin are restarted along with it
+        loop {
+            let dependents: Vec<String> = self
+                .instances
+                .iter()
+                .filter(|i| !stopping.contains(&i.config.id()))
+                .filter(|i| {
+                    let plugin = i.config.plugin();
+                    plugin
+                        .depends()
+                        .iter()
+                        .chain(plugin.optional_depends())
+                        .any(|d| stopping.contains(d))
+                })
+                .map(|i| i.config.id())
+                .collect();
+            if dependents.is_empty() {
+                break;
+            }
+            stopping.extend(dependents);
+        }
+
+        // the running plugins are in load order, so they're stopped from the back
+        let (stopped, running): (Vec<_>, Vec<_>) = self
+            .instances
+            .drain(..)
+            .partition(|i| stopping.contains(&i.config.id()));
+        self.instances = running;
+        for instance in stopped.iter() {
+            let id = instance.config.id();
+            if !reload.removed.contains(&id) && !reload.start.iter().any(|c| c.id() == id) {
+                reload.start.push(instance.config.clone());
+            }
+        }
+        let order = &self.order;
+        reload.start.sort_by_key(|c| {
+            order
+                .iter()
+                .position(|id| id == &c.id())
+                .unwrap_or(usize::MAX)
+        });
+        // anything sent to a plugin between its old instance stopping and its new one starting is
+        // kept for the new one, rather than lost
+        reload.held = reload.start.iter().map(|c| (c.id(), vec![])).collect();
+
+        self.reloading = Some(reload);
+
+        let events = self.context.events.clone();
+        instance::spawn(async move {
+            shutdown::stop_in_order(&stopped).await;
+            let _ = events.send(PluginEvent::Stopped { stopped });
+        });
+    }
+
+    /// Starts the plugins a reload replaced, once the old ones have stopped.
+    fn reload_stopped(&mut self, stopped: Vec<PluginInstance>) {
+        let mut reload = match self.reloading.take() {
+            Some(reload) => reload,
+            None => return,
+        };
+
+        for instance in stopped.iter().rev() {
+            self.broadcast(&instance.status(None).notification("plugin.stopped"));
+            if reload.removed.contains(&instance.config.id()) {
+                info!("Stopped plugin {}, which was removed", instance.config.id());
+            }
+        }
+
+        let mut reloaded = 0;
+        for config in reload.start {
+            let id = config.id();
+            let dependent = reload.id.as_ref().is_some_and(|reloaded| reloaded != &id);
+
+            // a reload is a fresh start, so forget about earlier crashes
+            self.restarts.retain(|(_, c)| c.id() != id);
+            self.crashes.remove(&id);
+
+            let started = self.start(config);
+            if let Some(held) = reload.held.remove(&id) {
+                if let Some(instance) = self.instances.iter().find(|i| i.config.id() == id) {
+                    for line in held {
+                        let _ = instance.stdin.send(line);
+                    }
+                }
+            }
+            match started {
+                Ok(()) if dependent => info!("Restarted plugin {}", id),
+                Ok(()) => {
+                    reloaded += 1;
+                    if reload.id.is_some() {
+                        info!("Reloaded plugin {}", id);
+                    }
+                }
+                Err(e) if dependent => warn!("Failed to restart plugin {}: {}", id, e),
+                Err(e) => warn!("Failed to reload plugin {}: {}", id, e),
+            }
+        }
+        if reload.id.is_none() {
+            info!("Reloaded {} plugins", reloaded);
+        }
+
+        self.finish_reload();
+    }
+
+    /// Whether messages for the plugin are being kept until a reload has restarted it.
+    fn holds(&self, id: &str) -> bool {
+        self.reloading
+            .as_ref()
+            .is_some_and(|reload| reload.held.contains_key(id))
+    }
+
+    /// Moves on to the next queued reload, if any.
+    fn finish_reload(&mut self) {
+        self.reloading = None;
+        if let Some(id) = self.queued_reloads.pop_front() {
+            self.reload(id);
+        }
+    }
+
+    /// Handles something that happened to a plugin, scheduling a restart if it exited by itself.
+    pub fn handle(&mut self, event: PluginEvent) {
+        match event {
+            PluginEvent::Reload {
+                id: Some(id),
+                reason,
+            } => {
+                info!("Reloading plugin {} ({})", id, reason);
+                self.reload(Some(id));
+            }
+            PluginEvent::Reload { id: None, reason } => {
+                info!("Reloading all plugins ({})", reason);
+                self.reload(None);
+            }
+            PluginEvent::Message {
+                from,
+                target,
+                message,
+            } => match self.instances.iter().find(|i| i.config.id() == target) {
+                Some(instance) => {
+                    instance.send(&PluginMessagePayload { from, message }.into());
+                }
+                None if self.holds(&target) => {
+                    let message: rpc::Message = PluginMessagePayload { from, message }.into();
+                    let line = serde_json::to_string(&message).unwrap();
+                    if let Some(reload) = &mut self.reloading {
+                        reload.held.get_mut(&target).unwrap().push(line);
+                    }
+                }
+                None => warn!(
+                    "Plugin {} sent a message to {}, which isn't running",
+                    from, target
+                ),
+            },
+            PluginEvent::Built { built, failed } => self.reload_built(built, failed),
+            PluginEvent::Stopped { stopped } => self.reload_stopped(stopped),
+            PluginEvent::Exited { id, status } => {
+                let index = match self.instances.iter().position(|i| i.config.id() == id) {
+                    Some(index) => index,
+                    None => return,
+                };
+                let instance = self.instances.remove(index);
+
+                let success = status.map(|s| s.success()).unwrap_or(false);
+                match status {
+                    Some(status) if success => info!("Plugin {} exited ({})", id, status),
+                    Some(status) => error!("Plugin {} exited unexpectedly ({})", id, status),
+                    None => error!("Plugin {} exited unexpectedly", id),
+                }
+
+                let exit_code = status.and_then(|s| s.code());
+                self.broadcast(&instance.status(exit_code).notification("plugin.stopped"));
+
+                let defaults = &config::get().plugins;
+                let policy = instance.config.plugin().restart();
+                let restart = match policy.unwrap_or(defaults.restart) {
+                    RestartPolicy::Always => true,
+                    RestartPolicy::OnFailure => !success,
+                    RestartPolicy::Never => false,
+                };
+                if !restart {
+                    return;
+                }
+
+                let crashes = self.crashes.entry(id.clone()).or_insert_with(|| {
+                    CrashTracker::new(defaults.max_restarts, defaults.crash_window())
+                });
+                match crashes.record() {
+                    Some(backoff) => {
+                        warn!("Restarting plugin {} in {}s", id, backoff.as_secs());
+                        self.restarts
+                            .push((Instant::now() + backoff, instance.config.clone()));
+                    }
+                    None => error!(
+                        "Plugin {} exited {} times in {}s, not restarting it",
+                        id, defaults.max_restarts, defaults.crash_window
+                    ),
+                }
+            }
+        }
+    }
+
+    /// When the next plugin is due to be restarted, if any are waiting.
+    pub fn next_restart(&self) -> Option<Instant> {
+        self.restarts.iter().map(|(at, _)| *at).min()
+    }
+
+    /// Restarts the plugins whose backoff has passed.
+    pub fn restart_due(&mut self) {
+        let now = Instant::now();
+        let (due, waiting) = self.restarts.drain(..).partition(|(at, _)| *at <= now);
+        self.restarts = waiting;
+
+        for (_, config) in due {
+            let id = config.id();
+            match self.start(config) {
+                Ok(()) => info!("Restarted plugin {}", id),
+                Err(e) => error!("Failed to restart plugin {}: {}", id, e),
+            }
+        }
+    }
+}
+
+/// Orders plugins so each starts after the plugins it depends on, leaving out those whose required
+/// dependencies are missing or cyclic. Ties are broken by id, so the order doesn't depend on the filesystem.
+pub fn load_order(mut configs: Vec<PluginConfig>) -> Vec<PluginConfig> {
+    configs.sort_by_key(|c| c.id());
+
+    // leaving out a plugin can leave its dependents without a dependency, so repeat until nothing changes
+    loop {
+        let ids: HashSet<String> = configs.iter().map(|c| c.id()).collect();
+        let (available, missing): (Vec<_>, Vec<_>) = configs
+            .into_iter()
+            .partition(|c| c.plugin.depends().iter().all(|d| ids.contains(d)));
+        configs = available;
+
+        if missing.is_empty() {
+            break;
+        }
+        for config in missing {
+            let absent: Vec<&str> = config
+                .plugin
+                .depends()
+                .iter()
+                .filter(|d| !ids.contains(*d))
+                .map(|d| &d[..])
+                .collect();
+            error!(
+                "Not starting plugin {}: it depends on {}, which isn't available",
+                config.id(),
+                absent.join(", ")
+            );
+        }
+    }
+
+    let mut ordered: Vec<PluginConfig> = vec![];
+    let mut remaining = configs;
+    while !remaining.is_empty() {
+        let waiting: HashSet<String> = remaining.iter().map(|c| c.id()).collect();
+        let waits_on = |deps: &[String]| deps.iter().any(|d| waiting.contains(d));
+
+        // optional dependencies only decide the order, so a cycle of them is broken rather than refused
+        let next = remaining
+            .iter()
+            .position(|c| !waits_on(c.plugin.depends()) && !waits_on(c.plugin.optional_depends()))
+            .or_else(|| remaining.iter().position(|c| !waits_on(c.plugin.depends())));
+
+        match next {
+            Some(index) => ordered.push(remaining.remove(index)),
+            None => {
+                for config in remaining {
+                    error!(
+                        "Not starting plugin {}: its dependencies are cyclic",
+                        config.id()
+                    );
+                }
+                break;
+            }
+        }
+    }
+
+    ordered
+}
+
+/// Watches each plugin's `plugin.toml` and program, asking for the plugin to be reloaded when they
+/// change, or started when it's added to the plugins folder.
+pub fn watch(plugins_dir: PathBuf, events: mpsc::UnboundedSender<PluginEvent>) {
+    instance::spawn(async move {
+        let (mut seen, mut sources) = watched_files(plugins_dir.clone(), HashMap::new()).await;
+        // why each plugin whose files haven't settled yet will be reloaded
+        let mut changed = HashMap::new();
+
+        loop {
+            sleep(WATCH_INTERVAL).await;
+
+            // wait for a changed plugin's files to settle, so a half-written binary isn't started
+            let current;
+            (current, sources) = watched_files(plugins_dir.clone(), sources).await;
+            for (id, modified) in current.iter() {
+                match seen.get(id) {
+                    Some(previous) if previous == modified => {
+                        let reason = match changed.remove(id) {
+                            Some(reason) => reason,
+                            None => continue,
+                        };
+                        let event = PluginEvent::Reload {
+                            id: Some(id.clone()),
+                            reason,
+                        };
+                        if events.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    Some(_) => {
+                        changed
+                            .entry(id.clone())
+                            .or_insert_with(|| "its files changed".into());
+                    }
+                    None => {
+                        changed.insert(id.clone(), "it was added".into());
+                    }
+                }
+            }
+            seen = current;
+        }
+    });
+}
+
+/// When each plugin's `plugin.toml` and program were last modified, by plugin id. The folders are
+/// read off the runtime's workers, as that blocks, and `sources` remembers what was found in them
+/// for the next time.
+async fn watched_files(
+    plugins_dir: PathBuf,
+    mut sources: HashMap<String, SourceFiles>,
+) -> (
+    HashMap<String, (Option<SystemTime>, Option<SystemTime>)>,
+    HashMap<String, SourceFiles>,
+) {
+    task::spawn_blocking(move || {
+        let mut files = HashMap::new();
+
+        if let Ok(paths) = std::fs::read_dir(&plugins_dir) {
+            for child in paths.filter_map(|child| child.ok()) {
+                let path = child.path();
+                let metadata_path = path.join("plugin.toml");
+                if !metadata_path.is_file() {
+                    continue;
+                }
+
+                let id = child.file_name().to_string_lossy().into_owned();
+                let config = std::fs::read_to_string(&metadata_path)
+                    .ok()
+                    .and_then(|contents| toml::from_str::<PluginConfig>(&contents).ok());
+                let program_modified = match config {
+                    Some(mut config) => {
+                        config.path = Some(path);
+                        program_modified(&config, sources.entry(id.clone()).or_default())
+                    }
+                    None => None,
+                };
+
+                files.insert(id, (modified(&metadata_path), program_modified));
+            }
+        }
+
+        // plugins that are gone don't need their sources remembered
+        sources.retain(|id, _| files.contains_key(id));
+        (files, sources)
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// When the plugin's program last changed. That's its target, unless it runs a command, in which
+/// case it's the files named in its arguments, or its sources if none are.
+fn program_modified(config: &PluginConfig, sources: &mut SourceFiles) -> Option<SystemTime> {
+    if config.plugin.command().is_none() {
+        return modified(&config.target_path()?);
+    }
+
+    let cwd = config.cwd()?;
+    let mut newest = None;
+    let mut named = false;
+    for arg in config.plugin.args() {
+        let path = cwd.join(arg);
+        if path.is_file() {
+            named = true;
+            newest = newest.max(modified(&path));
+        }
+    }
+
+    if named {
+        newest
+    } else {
+        sources.newest(config)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Sends the response to a request back to the plugin.
+fn reply(plugin_stdin: &UnboundedSender<String>, id: rpc::Id, result: Result<Value, RpcError>) {
+    let message = rpc::Message::reply(id, result);
+    let _ = plugin_stdin.send(serde_json::to_string(&message).unwrap());
+}
+
+/// Handles a message with `handler`, replying to the plugin if the message was a request.
+fn handle<F>(plugin_stdin: &UnboundedSender<String>, message: rpc::Message, handler: F)
+where
+    F: FnOnce(rpc::Message) -> Result<Value, RpcError>,
+{
+    let id = message.id().cloned();
+    let result = handler(message);
+    if let Some(id) = id {
+        reply(plugin_stdin, id, result);
+    }
+}
+
+fn internal_error(error: anyhow::Error) -> RpcError {
+    RpcError::new(RpcError::INTERNAL_ERROR, error.to_string())
+}
+
+fn player_not_found(target: &str) -> RpcError {
+    RpcError::new(
+        RpcError::PLAYER_NOT_FOUND,
+        format!("{} is not online", target),
+    )
+}
+
+/// Handles a `whisper` request, privately messaging a player the server knows to be online.
+fn whisper(
+    message: rpc::Message,
+    players: &PlayerList,
+    game_stdin: &UnboundedSender<String>,
+) -> Result<Value, RpcError> {
+    let payload: payloads::WhisperPayload = message.try_into()?;
+    let player = players
+        .find(&payload.target)
+        .ok_or_else(|| player_not_found(&payload.target))?;
+
+    for line in payload
+        .content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+    {
+        game_stdin
+            .send(format!("Chat.Whisper \"{}\" {}", player.name, line))
+            .unwrap();
+    }
+
+    Ok(Value::Null)
+}
+
+/// Handles a `players.kick` request.
+fn kick(
+    message: rpc::Message,
+    players: &PlayerList,
+    game_stdin: &UnboundedSender<String>,
+) -> Result<Value, RpcError> {
+    let payload: payloads::KickPayload = message.try_into()?;
+    let player = players
+        .find(&payload.target)
+        .ok_or_else(|| player_not_found(&payload.target))?;
+
+    info!("Kicking {} ({})", player.name, payload.reason);
+    game_stdin
+        .send(players::kick_command(&player.name, &payload.reason))
+        .unwrap();
+
+    Ok(Value::Null)
+}
+
+/// Handles a `players.ban` request, recording the ban and kicking the player if they're online.
+fn ban(
+    message: rpc::Message,
+    issuer: &str,
+    players: &PlayerList,
+    bans: &BanList,
+    game_stdin: &UnboundedSender<String>,
+) -> Result<Value, RpcError> {
+    let payload: payloads::BanPayload = message.try_into()?;
+    let online = players.find(&payload.target);
+    let (uuid, name) = match (&online, payload.target.parse()) {
+        (Some(player), _) => (player.uuid, player.name.clone()),
+        (None, Ok(uuid)) => (uuid, String::new()),
+        (None, Err(_)) => return Err(player_not_found(&payload.target)),
+    };
+
+    let created = bans::now();
+    let ban = Ban {
+        uuid,
+        name,
+        reason: payload.reason,
+        issuer: issuer.into(),
+        created,
+        expires: payload.duration.map(|duration| created + duration),
+    };
+
+    info!("{} banned {} ({})", issuer, payload.target, ban.reason);
+    bans.ban(ban.clone()).map_err(internal_error)?;
+
+    if let Some(player) = online {
+        game_stdin
+            .send(players::kick_command(&player.name, &ban.reason))
+            .unwrap();
+    }
+
+    Ok(Value::Null)
+}
+
+/// Handles a `players.unban` request.
+fn unban(message: rpc::Message, bans: &BanList) -> Result<Value, RpcError> {
+    let payload: payloads::UnbanPayload = message.try_into()?;
+    if payload.target.trim().is_empty() {
+        return Err(RpcError::new(
+            RpcError::INVALID_PARAMS,
+            "no player to unban was given",
+        ));
+    }
+
+    match bans.unban(&payload.target) {
+        Ok(Some(ban)) => {
+            info!("Unbanned {} ({})", payload.target, ban.uuid);
+            Ok(Value::Null)
+        }
+        Ok(None) => Err(RpcError::new(
+            RpcError::NOT_BANNED,
+            format!("{} is not banned", payload.target),
+        )),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+/// Scan the plugins folder for plugins, and generate a list of them. Their data is kept in
+/// `data_dir`.
+pub async fn scan(plugins_dir: &Path, data_dir: &Path) -> Vec<PluginConfig> {
+    let mut plugins = vec![];
+
+    let mut paths = match fs::read_dir(plugins_dir).await {
+        Ok(paths) => paths,
+        Err(_) => {
+            warn!("Plugins folder doesn't exist, couldn't find any plugins");
+            return vec![];
+        }
+    };
+
     while let Some(child) = paths.next_entry().await.unwrap() {
         let path = child.path();
         let metadata_path = path.join("plugin.toml");
@@ -203,39 +1305,48 @@ pub async fn scan() -> Vec<PluginConfig> {
             continue;
         }
 
-        let file = File::open(&metadata_path).await;
-        if let Err(_) = file {
-            warn!(
-                "Failed to read plugin metadata at {} (opening)",
-                metadata_path.to_str().unwrap()
-            );
-            continue;
-        }
-
-        let mut file = file.unwrap();
-        let mut contents = String::new();
-        match file.read_to_string(&mut contents).await {
+        let mut file = match File::open(&metadata_path).await {
+            Ok(file) => file,
             Err(_) => {
                 warn!(
-                    "Failed to read plugin metadata at {} (reading)",
+                    "Failed to read plugin metadata at {} (opening)",
                     metadata_path.to_str().unwrap()
                 );
                 continue;
             }
-            _ => (),
+        };
+
+        let mut contents = String::new();
+        if file.read_to_string(&mut contents).await.is_err() {
+            warn!(
+                "Failed to read plugin metadata at {} (reading)",
+                metadata_path.to_str().unwrap()
+            );
+            continue;
         }
 
-        let mut plugin: PluginConfig = match toml::from_str(&contents[..]) {
-            Ok(p) => p,
-            Err(_) => {
-                warn!("Bad plugin metadata at {}", metadata_path.to_str().unwrap());
+        let (mut plugin, unknown) = match check::parse(&contents) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                warn!(
+                    "Bad plugin metadata at {}: {}",
+                    metadata_path.to_str().unwrap(),
+                    e
+                );
                 continue;
             }
         };
 
+        for key in unknown {
+            warn!("Unknown key {} in {}", key, metadata_path.to_str().unwrap());
+        }
+
         plugin.path = Some(path);
+        plugin.instance_data_dir = data_dir.to_path_buf();
         plugins.push(plugin);
     }
 
+    // read_dir's order depends on the filesystem
+    plugins.sort_by_key(|p| p.id());
     plugins
 }
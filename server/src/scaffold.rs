@@ -0,0 +1,125 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use log::{error, info};
+use regex::Regex;
+
+use crate::config;
+
+/// The kinds of plugin `server plugin new` can generate.
+pub const TEMPLATES: &[&str] = &["rust", "script"];
+
+/// The files each template is made of, as (path in the plugin folder, contents).
+const RUST_FILES: &[(&str, &str)] = &[
+    ("Cargo.toml", include_str!("../templates/rust/Cargo.toml")),
+    ("plugin.toml", include_str!("../templates/rust/plugin.toml")),
+    ("README.md", include_str!("../templates/rust/README.md")),
+    ("src/main.rs", include_str!("../templates/rust/main.rs")),
+    (".gitignore", "target/\n"),
+];
+const SCRIPT_FILES: &[(&str, &str)] = &[
+    (
+        "plugin.toml",
+        include_str!("../templates/script/plugin.toml"),
+    ),
+    ("README.md", include_str!("../templates/script/README.md")),
+    ("plugin.py", include_str!("../templates/script/plugin.py")),
+];
+
+/// Creates a plugin called `name` in the plugins folder from a template, returning its folder.
+pub fn new_plugin(name: &str, template: &str) -> Result<PathBuf> {
+    // the name doubles as the plugin's id and, for rust plugins, its crate and binary name
+    if !Regex::new("^[A-Za-z][A-Za-z0-9_-]*$")
+        .unwrap()
+        .is_match(name)
+    {
+        bail!("plugin names must start with a letter and contain only letters, digits, - and _");
+    }
+
+    let dir = config::get().plugins_dir.join(name);
+    if dir.exists() {
+        bail!("{} already exists", dir.display());
+    }
+
+    let files = match template {
+        "rust" => RUST_FILES,
+        "script" => SCRIPT_FILES,
+        _ => bail!(
+            "unknown template {}, expected one of {}",
+            template,
+            TEMPLATES.join(", ")
+        ),
+    };
+
+    fs::create_dir_all(&dir)?;
+    let author = env::var("USER").unwrap_or_else(|_| "unknown".into());
+    let sdk = sdk_path(&dir);
+    for (path, contents) in files {
+        let contents = contents
+            .replace("{{name}}", name)
+            .replace("{{author}}", &author)
+            .replace("{{sdk}}", &sdk);
+
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+    }
+
+    Ok(dir)
+}
+
+/// Where the rust plugin in `dir` finds the `plugin` crate: relative to the plugin when the server
+/// runs from a checkout of this repository, or wherever the server was built from otherwise.
+fn sdk_path(dir: &Path) -> String {
+    if Path::new("plugin/Cargo.toml").is_file() {
+        if let (Ok(dir), Ok(sdk)) = (fs::canonicalize(dir), fs::canonicalize("plugin")) {
+            return relative_path(&dir, &sdk).display().to_string();
+        }
+    }
+
+    let built_from = Path::new(env!("CARGO_MANIFEST_DIR")).join("../plugin");
+    fs::canonicalize(&built_from)
+        .unwrap_or(built_from)
+        .display()
+        .to_string()
+}
+
+/// The path to `to` from the folder `from`, both absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let shared = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = PathBuf::new();
+    for _ in shared..from.len() {
+        path.push("..");
+    }
+    path.extend(&to[shared..]);
+    path
+}
+
+/// Runs `server plugin new`, returning the exit code.
+pub fn run(name: &str, template: &str) -> i32 {
+    match new_plugin(name, template) {
+        Ok(dir) => {
+            info!("Created {}", dir.display());
+            if template == "rust" {
+                info!("Build it with cargo build in that folder, then start the server");
+            }
+            info!(
+                "Approve its capabilities from the console with .approve {}",
+                name
+            );
+            0
+        }
+        Err(e) => {
+            error!("Failed to create plugin {}: {}", name, e);
+            1
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local};
+use plugin::{
+    payloads::{ScheduleCreatePayload, ScheduleFiredPayload},
+    rpc,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle, time::sleep};
+
+use crate::{files, instance};
+
+/// The name of the file persisted schedules are kept in, inside the plugin's data folder.
+pub const SCHEDULES_FILE: &str = "schedules.json";
+/// The shortest interval a schedule can fire at, so a plugin can't flood itself.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When a schedule fires.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    /// On a cron expression, in local time.
+    Cron(String),
+    /// At `start`, then every so many seconds after it.
+    Every { start: DateTime<Local>, every: f64 },
+    /// Once, at the given time. A time missed while the server was down fires as soon as it's back.
+    Once(DateTime<Local>),
+}
+
+impl Trigger {
+    /// The first time the trigger fires after `after`, if it ever does.
+    fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Trigger::Cron(expression) => cron_schedule(expression).ok()?.after(&after).next(),
+            Trigger::Every { start, every } => {
+                if *start > after {
+                    return Some(*start);
+                }
+                let every = chrono::Duration::from_std(Duration::from_secs_f64(*every)).ok()?;
+                let elapsed = (after - *start).num_milliseconds() / every.num_milliseconds();
+                Some(*start + every * (elapsed as i32 + 1))
+            }
+            Trigger::Once(at) => Some(*at),
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Cron(expression) => write!(f, "cron {}", expression),
+            Trigger::Every { every, .. } => write!(
+                f,
+                "every {}",
+                humantime::format_duration(Duration::from_secs_f64(*every))
+            ),
+            Trigger::Once(_) => write!(f, "once"),
+        }
+    }
+}
+
+/// Parses a cron expression, which may leave out the seconds field.
+fn cron_schedule(expression: &str) -> Result<cron::Schedule> {
+    let expression = match expression.split_whitespace().count() {
+        5 => format!("0 {}", expression),
+        _ => expression.to_owned(),
+    };
+    cron::Schedule::from_str(&expression).map_err(|e| anyhow!("invalid cron expression: {}", e))
+}
+
+/// One of a plugin's schedules.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Schedule {
+    pub name: String,
+    pub trigger: Trigger,
+    /// Whether the schedule is kept when the plugin or the server restarts.
+    #[serde(skip)]
+    pub persist: bool,
+}
+
+impl Schedule {
+    /// Makes a schedule from what a plugin asked for in `schedule.create`.
+    pub fn new(payload: ScheduleCreatePayload) -> Result<Self> {
+        let seconds = |name: &str, value: Option<f64>| match value {
+            Some(value) if !value.is_finite() || value < 0.0 => {
+                bail!("{} should be a positive number of seconds", name)
+            }
+            Some(value) => Ok(Some(Duration::from_secs_f64(value))),
+            None => Ok(None),
+        };
+        let every = seconds("every", payload.every)?;
+        let delay = seconds("delay", payload.delay)?;
+        let now = Local::now();
+        let after = |delay: Duration| -> Result<DateTime<Local>> {
+            Ok(now + chrono::Duration::from_std(delay)?)
+        };
+
+        let trigger = match (payload.cron, every, delay) {
+            (Some(expression), None, None) => {
+                cron_schedule(&expression)?;
+                Trigger::Cron(expression)
+            }
+            (Some(_), _, _) => bail!("a cron schedule can't also have every or delay"),
+            (None, Some(every), _) if every < MIN_INTERVAL => bail!(
+                "every should be at least {}",
+                humantime::format_duration(MIN_INTERVAL)
+            ),
+            (None, Some(every), delay) => Trigger::Every {
+                start: after(delay.unwrap_or(every))?,
+                every: every.as_secs_f64(),
+            },
+            (None, None, Some(delay)) => Trigger::Once(after(delay)?),
+            (None, None, None) => bail!("a schedule needs cron, every or delay"),
+        };
+
+        Ok(Schedule {
+            name: payload.name,
+            trigger,
+            persist: payload.persist,
+        })
+    }
+
+    /// The next time the schedule fires.
+    pub fn next(&self) -> Option<DateTime<Local>> {
+        self.trigger.next_after(Local::now())
+    }
+}
+
+/// A running plugin's schedules, each waiting on its next firing in its own task.
+#[derive(Clone)]
+pub struct Schedules {
+    path: PathBuf,
+    /// Where `schedule.fired` notifications go, which is the plugin's stdin.
+    plugin_stdin: UnboundedSender<String>,
+    schedules: Arc<Mutex<Running>>,
+}
+
+/// Each schedule by name, with the task that fires it.
+type Running = BTreeMap<String, (Schedule, JoinHandle<()>)>;
+
+impl Schedules {
+    /// Loads the persisted schedules in `data_dir` and starts them.
+    pub fn load(data_dir: &Path, plugin_stdin: UnboundedSender<String>) -> Result<Self> {
+        let path = data_dir.join(SCHEDULES_FILE);
+        let persisted: Vec<Schedule> = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+
+        let schedules = Schedules {
+            path,
+            plugin_stdin,
+            schedules: Arc::new(Mutex::new(BTreeMap::new())),
+        };
+        let mut running = schedules.schedules.lock().unwrap();
+        for mut schedule in persisted {
+            schedule.persist = true;
+            let task = schedules.spawn(schedule.clone());
+            running.insert(schedule.name.clone(), (schedule, task));
+        }
+        drop(running);
+
+        Ok(schedules)
+    }
+
+    /// Starts a schedule, replacing the one with the same name. Persisted schedules are only
+    /// started once they are saved.
+    pub fn create(&self, schedule: Schedule) -> Result<()> {
+        let mut schedules = self.schedules.lock().unwrap();
+        let mut persisted = persisted(&schedules);
+        persisted.retain(|s| s.name != schedule.name);
+        if schedule.persist {
+            persisted.push(schedule.clone());
+        }
+        if schedule.persist
+            || schedules
+                .get(&schedule.name)
+                .is_some_and(|(s, _)| s.persist)
+        {
+            save(&self.path, &persisted)?;
+        }
+
+        let task = self.spawn(schedule.clone());
+        if let Some((_, previous)) = schedules.insert(schedule.name.clone(), (schedule, task)) {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    /// Cancels a schedule, returning whether it existed.
+    pub fn cancel(&self, name: &str) -> Result<bool> {
+        let mut schedules = self.schedules.lock().unwrap();
+        let persist = match schedules.get(name) {
+            Some((schedule, _)) => schedule.persist,
+            None => return Ok(false),
+        };
+        if persist {
+            let mut persisted = persisted(&schedules);
+            persisted.retain(|s| s.name != name);
+            save(&self.path, &persisted)?;
+        }
+
+        if let Some((_, task)) = schedules.remove(name) {
+            task.abort();
+        }
+        Ok(true)
+    }
+
+    /// The plugin's schedules, by name.
+    pub fn list(&self) -> Vec<Schedule> {
+        self.schedules
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(schedule, _)| schedule.clone())
+            .collect()
+    }
+
+    /// Stops every schedule without forgetting the persisted ones, for when the plugin exits.
+    pub fn stop(&self) {
+        for (_, (_, task)) in std::mem::take(&mut *self.schedules.lock().unwrap()) {
+            task.abort();
+        }
+    }
+
+    fn spawn(&self, schedule: Schedule) -> JoinHandle<()> {
+        let schedules = self.clone();
+        instance::spawn(async move {
+            let mut after = Local::now();
+            while let Some(next) = schedule.trigger.next_after(after) {
+                sleep((next - Local::now()).to_std().unwrap_or_default()).await;
+                let notification: rpc::Message = ScheduleFiredPayload {
+                    name: schedule.name.clone(),
+                }
+                .into();
+                if schedules
+                    .plugin_stdin
+                    .send(serde_json::to_string(&notification).unwrap())
+                    .is_err()
+                {
+                    break;
+                }
+
+                // counting from the last firing rather than now, so a late wakeup can't fire twice
+                after = next.max(Local::now());
+                if let Trigger::Once(at) = schedule.trigger {
+                    // this task is done, so it's removed rather than aborted, unless it was
+                    // replaced while firing
+                    let mut running = schedules.schedules.lock().unwrap();
+                    if matches!(running.get(&schedule.name), Some((s, _)) if matches!(s.trigger, Trigger::Once(other) if other == at))
+                    {
+                        running.remove(&schedule.name);
+                        if schedule.persist {
+                            let _ = save(&schedules.path, &persisted(&running));
+                        }
+                    }
+                    break;
+                }
+            }
+        })
+    }
+}
+
+fn persisted(schedules: &Running) -> Vec<Schedule> {
+    schedules
+        .values()
+        .map(|(schedule, _)| schedule)
+        .filter(|schedule| schedule.persist)
+        .cloned()
+        .collect()
+}
+
+fn save(path: &Path, schedules: &[Schedule]) -> Result<()> {
+    files::atomic_write(path, serde_json::to_string_pretty(schedules)?.as_bytes())
+}
@@ -1,59 +1,138 @@
-use std::env;
-use std::process::Stdio;
+use std::{collections::VecDeque, env, path::Path, process::Stdio, sync::Arc, time::Duration};
 
 use log::error;
+
+use crate::{config, instance};
 use tokio::{
-    io::AsyncWriteExt,
-    process::{Child, Command},
-    sync::mpsc,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdout, Command},
+    sync::{mpsc, Mutex},
     task::JoinHandle,
+    time::Instant,
 };
 
+/// How long to wait before restarting the game after its first crash. Doubles with each crash.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// The longest wait before restarting the game.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The command used to launch the game server.
+#[derive(Debug, Clone)]
+pub struct GameCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl GameCommand {
+    /// The Brickadia launcher, with its output line-buffered through `stdbuf`.
+    pub fn launcher() -> Self {
+        GameCommand {
+            program: "stdbuf".into(),
+            args: vec![
+                "--output=L".into(),
+                "--".into(),
+                config::launcher_path()
+                    .join("main-brickadia-launcher")
+                    .to_string_lossy()
+                    .into_owned(),
+                "--server".into(),
+                "--".into(),
+            ],
+        }
+    }
+
+    /// Parses a whitespace separated command line, e.g. `fake_brickadia --script join.txt`.
+    pub fn parse(command: &str) -> Option<Self> {
+        let mut words = command.split_whitespace().map(String::from);
+        let program = words.next()?;
+
+        Some(GameCommand {
+            program,
+            args: words.collect(),
+        })
+    }
+}
+
+/// Decides how long to wait before restarting the game (or a plugin) after it exits unexpectedly,
+/// or whether to give up because it crashed too often.
+pub struct CrashTracker {
+    max_crashes: usize,
+    window: Duration,
+    crashes: VecDeque<Instant>,
+}
+
+impl CrashTracker {
+    /// Gives up once there are `max_crashes` crashes within `window`.
+    pub fn new(max_crashes: usize, window: Duration) -> Self {
+        CrashTracker {
+            max_crashes,
+            window,
+            crashes: VecDeque::new(),
+        }
+    }
+
+    /// Records a crash, returning how long to back off before restarting, or None to give up.
+    pub fn record(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        self.crashes.push_back(now);
+        while let Some(first) = self.crashes.front() {
+            if now.duration_since(*first) <= self.window {
+                break;
+            }
+            self.crashes.pop_front();
+        }
+
+        let recent = self.crashes.len();
+        if recent >= self.max_crashes {
+            return None;
+        }
+
+        let backoff = INITIAL_BACKOFF * 2u32.saturating_pow(recent as u32 - 1);
+        Some(backoff.min(MAX_BACKOFF))
+    }
+}
+
 pub struct Server {
     pub child: Child,
     pub stdin_task: JoinHandle<()>,
 }
 
 impl Server {
+    /// Starts the game, keeping its saves in `data_dir`. The stdin receiver is shared so that a
+    /// restarted game picks up where the last one left off.
     pub fn start(
-        args: &Vec<String>,
-        mut stdin_receiver: mpsc::UnboundedReceiver<String>,
+        command: &GameCommand,
+        args: &[String],
+        data_dir: &Path,
+        stdin_receiver: Arc<Mutex<mpsc::UnboundedReceiver<String>>>,
     ) -> Result<Self, std::io::Error> {
-        let mut data_location = env::current_dir()?;
-        data_location.push("data");
+        let data_location = env::current_dir()?.join(data_dir);
 
-        let mut child = Command::new("stdbuf")
+        let mut child = Command::new(&command.program)
             .env(
                 "LD_LIBRARY_PATH",
-                format!("{}:$LD_LIBRARY_PATH", launcher::LAUNCHER_PATH),
+                format!("{}:$LD_LIBRARY_PATH", config::launcher_path().display()),
             )
-            .arg("--output=L")
-            .arg("--")
-            .arg(format!(
-                "{}/{}",
-                launcher::LAUNCHER_PATH,
-                "main-brickadia-launcher"
-            ))
-            .arg("--server")
-            .arg("--")
+            .args(&command.args)
             .arg("-NotInstalled")
             .arg("-log")
             .arg(format!("-UserDir={}", data_location.to_str().unwrap()))
             .args(args)
+            .kill_on_drop(true)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()?;
 
         let mut stdin = child.stdin.take().unwrap();
-        let stdin_task = tokio::spawn(async move {
+        let stdin_task = instance::spawn(async move {
+            let mut stdin_receiver = stdin_receiver.lock().await;
             while let Some(mut line) = stdin_receiver.recv().await {
                 line.push('\n');
 
                 // write to stdin, killing task if we fail to write
-                match stdin.write_all(&line[..].as_bytes()).await {
-                    Ok(_) => (),
-                    Err(_) => break,
+                if stdin.write_all(line.as_bytes()).await.is_err() {
+                    break;
                 }
             }
             error!("server stdin task died");
@@ -61,4 +140,10 @@ impl Server {
 
         Ok(Server { child, stdin_task })
     }
+
+    /// Takes the lines of the game's stdout.
+    pub fn lines(&mut self) -> Lines<BufReader<ChildStdout>> {
+        let stdout = self.child.stdout.take().unwrap();
+        BufReader::new(stdout).lines()
+    }
 }
@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::{
+    sync::mpsc,
+    time::{timeout, Instant},
+};
+
+use crate::{config, plugins::PluginInstance, server::Server};
+
+/// How long the game gets to save and exit before it is killed.
+const GAME_EXIT_TIMEOUT: Duration = Duration::from_secs(30);
+/// The name of the save the game makes on its way out.
+const SHUTDOWN_SAVE_NAME: &str = "brixide_shutdown";
+
+/// The wrapper's exit code when everything stopped by itself.
+pub const EXIT_CLEAN: i32 = 0;
+/// The wrapper's exit code when something had to be killed.
+pub const EXIT_KILLED: i32 = 1;
+/// The wrapper's exit code when it gave up restarting the game after too many crashes.
+pub const EXIT_CRASHED: i32 = 2;
+/// The wrapper's exit code when the game failed to log in with the credentials it was given.
+pub const EXIT_AUTH_FAILED: i32 = 3;
+
+/// Resolves when the wrapper is asked to stop, by SIGINT or SIGTERM.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = terminate.recv() => (),
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Stops the plugins and then the game, returning the wrapper's exit code.
+///
+/// Plugins get a `shutdown` notification and a grace period to exit, then the game is asked to
+/// save and exit through its console. Anything still running after its timeout is killed.
+pub async fn stop(
+    instances: &[PluginInstance],
+    game_stdin: &mpsc::UnboundedSender<String>,
+    server: &mut Server,
+) -> i32 {
+    let plugins_exited = stop_plugins(instances).await;
+    let game_exited = stop_game(game_stdin, server).await;
+
+    if plugins_exited && game_exited {
+        EXIT_CLEAN
+    } else {
+        EXIT_KILLED
+    }
+}
+
+/// Stops the plugins, returning false if any had to be killed.
+pub async fn stop_plugins(instances: &[PluginInstance]) -> bool {
+    info!("Stopping {} plugins", instances.len());
+    stop_in_order(instances).await
+}
+
+/// Stops plugins one at a time from last to first, so each plugin outlives the plugins that
+/// depend on it. Each is told to shut down and gets the grace period to exit before it's killed.
+/// Returns false if any had to be killed.
+pub async fn stop_in_order(instances: &[PluginInstance]) -> bool {
+    let mut exited = true;
+
+    for instance in instances.iter().rev() {
+        instance.shutdown();
+        let deadline = Instant::now() + config::get().plugins.grace_period();
+        if !instance.wait_until(deadline).await {
+            warn!(
+                "Plugin {} didn't exit in time, killing it",
+                instance.config.id()
+            );
+            instance.stop().await;
+            exited = false;
+        }
+    }
+
+    exited
+}
+
+/// Asks the game to save and exit, killing it if it doesn't in time. Returns false if it had to be killed.
+async fn stop_game(game_stdin: &mpsc::UnboundedSender<String>, server: &mut Server) -> bool {
+    info!("Saving and stopping the game");
+    let _ = game_stdin.send(format!("Bricks.Save \"{}\"", SHUTDOWN_SAVE_NAME));
+    let _ = game_stdin.send("exit".into());
+    match timeout(GAME_EXIT_TIMEOUT, server.child.wait()).await {
+        Ok(Ok(status)) => {
+            info!("The game exited ({})", status);
+            true
+        }
+        Ok(Err(e)) => {
+            warn!("Failed to wait for the game to exit: {}", e);
+            true
+        }
+        Err(_) => {
+            warn!("The game didn't exit in time, killing it");
+            let _ = server.child.kill().await;
+            false
+        }
+    }
+}
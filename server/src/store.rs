@@ -0,0 +1,91 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::files;
+
+/// The name of the file a plugin's store is kept in, inside the plugin's data folder.
+pub const STORE_FILE: &str = "store.json";
+
+/// A plugin's durable key-value store, persisted as JSON in its data folder.
+#[derive(Clone)]
+pub struct Store {
+    path: PathBuf,
+    values: Arc<Mutex<BTreeMap<String, Value>>>,
+}
+
+impl Store {
+    /// Loads the store in `data_dir`, or starts an empty one if it doesn't exist yet.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(STORE_FILE);
+        let values = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Store {
+            path,
+            values: Arc::new(Mutex::new(values)),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.values.lock().unwrap().get(key).cloned()
+    }
+
+    /// Sets a key, which is only kept in memory if it was saved. Setting a key to null deletes it.
+    pub fn set(&self, key: &str, value: Value) -> Result<()> {
+        if value.is_null() {
+            return self.delete(key).map(|_| ());
+        }
+
+        let mut values = self.values.lock().unwrap();
+        let previous = values.insert(key.into(), value);
+
+        if let Err(e) = save(&self.path, &values) {
+            match previous {
+                Some(previous) => values.insert(key.into(), previous),
+                None => values.remove(key),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Deletes a key, returning whether it existed.
+    pub fn delete(&self, key: &str) -> Result<bool> {
+        let mut values = self.values.lock().unwrap();
+        let previous = match values.remove(key) {
+            Some(previous) => previous,
+            None => return Ok(false),
+        };
+
+        if let Err(e) = save(&self.path, &values) {
+            values.insert(key.into(), previous);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// The keys starting with `prefix`, in order.
+    pub fn list(&self, prefix: &str) -> Vec<String> {
+        self.values
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+fn save(path: &Path, values: &BTreeMap<String, Value>) -> Result<()> {
+    files::atomic_write(path, serde_json::to_string_pretty(values)?.as_bytes())
+}
@@ -6,6 +6,7 @@ use anyhow::{bail, Result};
 use tokio::process::{Child, Command};
 
 pub struct UdpProxy {
+    #[allow(dead_code)] // held so the proxy is killed when dropped
     pub child: Child,
 }
 
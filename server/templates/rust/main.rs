@@ -0,0 +1,62 @@
+use std::convert::TryInto;
+
+use log::{info, warn};
+use plugin::{
+    payloads::ChatPayload,
+    player::Player,
+    rpc,
+    text::{Span, Text},
+    Plugin,
+};
+
+#[tokio::main]
+async fn main() {
+    Plugin::use_plugin_logger().unwrap();
+
+    let mut receiver = Plugin::spawn_listener();
+    info!("Started");
+
+    while let Some(message) = receiver.recv().await {
+        match message.method() {
+            Some("connect") => {
+                // a player is connecting
+                let player: Player = match message {
+                    rpc::Message::Notification {
+                        params: Some(params),
+                        ..
+                    } => match serde_json::from_value(params) {
+                        Ok(player) => player,
+                        Err(_) => continue,
+                    },
+                    _ => continue,
+                };
+
+                Plugin::broadcast_text(
+                    &Text::new()
+                        .push("Welcome, ")
+                        .push(Span::new(player.name).bold())
+                        .push("!"),
+                );
+            }
+            Some("chat") => {
+                // a player chats
+                let payload: ChatPayload = match message.try_into() {
+                    Ok(payload) => payload,
+                    Err(_) => continue,
+                };
+
+                if payload.message.trim() == "!hello" {
+                    let reply = Text::from("Hello from {{name}}!");
+                    if let Err(e) = Plugin::whisper_text(&payload.user, &reply).await {
+                        warn!("Couldn't reply to {}: {}", payload.user, e);
+                    }
+                }
+            }
+            Some("shutdown") => {
+                // the server is stopping, so exit before it has to kill us
+                break;
+            }
+            _ => (),
+        }
+    }
+}
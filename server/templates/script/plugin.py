@@ -0,0 +1,57 @@
+#!/usr/bin/env python3
+"""{{name}}, a brixide plugin. The server sends JSON-RPC messages on stdin, one per line,
+and reads the plugin's messages from stdout."""
+
+import json
+import sys
+
+next_id = 0
+
+
+def send(message):
+    message["jsonrpc"] = "2.0"
+    print(json.dumps(message), flush=True)
+
+
+def notify(method, params=None):
+    send({"method": method, "params": params})
+
+
+def request(method, params=None):
+    # responses come back on stdin, but this plugin doesn't wait for them
+    global next_id
+    next_id += 1
+    send({"id": next_id, "method": method, "params": params})
+
+
+def log(content, severity="Info"):
+    notify("log", {"severity": severity, "content": content})
+
+
+def escape(text):
+    """Escapes untrusted text, like player names, for use in chat markup."""
+    replacements = {"&": "&amp;", ";": "&scl;", "<": "&lt;", ">": "&gt;", '"': "&quot;", "\\": "\\\\"}
+    return "".join(replacements.get(c, c if c.isprintable() else " ") for c in text)
+
+
+log("Started")
+
+for line in sys.stdin:
+    try:
+        message = json.loads(line)
+    except ValueError:
+        continue
+
+    method = message.get("method")
+    params = message.get("params")
+
+    if method == "connect":
+        # a player is connecting
+        notify("broadcast", "Welcome, <b>%s</>!" % escape(params["name"]))
+    elif method == "chat":
+        # a player chats
+        if params["message"].strip() == "!hello":
+            request("whisper", {"target": params["user"], "content": "Hello from {{name}}!"})
+    elif method == "shutdown":
+        # the server is stopping, so exit before it has to kill us
+        break
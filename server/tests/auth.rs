@@ -0,0 +1,151 @@
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use common::Harness;
+
+const CREDENTIALS: &str = "email = \"host@example.com\"\npassword = \"hunter2\"\n";
+
+/// A harness whose game hasn't logged in yet, with the account in data/credentials.toml.
+fn unauthenticated(name: &str, mode: u32) -> Harness {
+    let harness = Harness::new(name);
+    fs::remove_dir_all(harness.dir.join("data/Saved/Auth")).unwrap();
+    let path = harness.dir.join("data/credentials.toml");
+    fs::write(&path, CREDENTIALS).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    harness
+}
+
+#[test]
+fn refuses_credentials_other_users_can_read() {
+    let mut harness = unauthenticated("refuses_credentials_other_users_can_read", 0o644);
+
+    harness.start();
+    assert_eq!(harness.wait_for_exit().code(), Some(1));
+    harness.expect_output("can be read by other users");
+    assert!(harness.game_args().is_empty(), "the game was started");
+}
+
+#[test]
+fn fails_without_credentials_when_headless() {
+    let mut harness = Harness::new("fails_without_credentials_when_headless");
+    fs::remove_dir_all(harness.dir.join("data/Saved/Auth")).unwrap();
+
+    harness.start();
+    assert_eq!(harness.wait_for_exit().code(), Some(1));
+    harness.expect_output("--credentials-stdin");
+}
+
+#[test]
+fn hands_credentials_over_in_a_private_file() {
+    let mut harness = unauthenticated("hands_credentials_over_in_a_private_file", 0o600);
+    harness.script("wait 1000\nlog LogTemp: Still here\nwait 60000\n");
+    // one left behind by a crash, which anyone could read
+    let leftover = harness.dir.join("data/credentials.cmdline.txt");
+    fs::write(&leftover, "stale").unwrap();
+    fs::set_permissions(&leftover, fs::Permissions::from_mode(0o644)).unwrap();
+
+    harness.start();
+    harness.expect_output("Server active");
+
+    harness.wait_for_file(&harness.dir.join("args.txt"), |args| !args.is_empty());
+    let args = harness.game_args();
+    assert!(
+        args.iter().all(|arg| !arg.contains("hunter2")),
+        "{:?}",
+        args
+    );
+    let file = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("-CmdLineFile="))
+        .unwrap_or_else(|| panic!("no -CmdLineFile in {:?}", args))
+        .to_owned();
+    let mode = fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "-User=\"host@example.com\" -Password=\"hunter2\""
+    );
+
+    // once the game has logged in, the file is removed
+    fs::create_dir_all(harness.dir.join("data/Saved/Auth")).unwrap();
+    harness.expect_output("credentials are no longer needed");
+    assert!(!Path::new(&file).exists());
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn stops_when_the_game_fails_to_log_in() {
+    let mut harness = unauthenticated("stops_when_the_game_fails_to_log_in", 0o600);
+    harness.script("log LogAuth: Error: Login failed: invalid email or password\n");
+
+    harness.start();
+    assert_eq!(
+        harness.wait_for_exit().code(),
+        Some(3),
+        "{}",
+        harness.output()
+    );
+    harness.expect_output("failed to log in with the credentials from");
+    assert!(!harness.dir.join("data/credentials.cmdline.txt").exists());
+}
+
+#[test]
+fn keeps_credentials_from_the_environment_to_itself() {
+    let mut harness = Harness::new("keeps_credentials_from_the_environment_to_itself");
+    fs::remove_dir_all(harness.dir.join("data/Saved/Auth")).unwrap();
+    let env_file = harness.dir.join("plugin_env.txt");
+    let received = harness.recording_plugin(
+        "dumper",
+        &format!("env > {}.tmp && mv {0}.tmp {0}\n", env_file.display()),
+    );
+    harness.plugin_with(
+        "builder",
+        &harness.dir.join("plugins/builder/never_built"),
+        "build = \"env > build_env.txt; exit 1\"\n",
+    );
+
+    let env = [
+        ("BRIXIDE_EMAIL", "host@example.com"),
+        ("BRIXIDE_PASSWORD", "hunter2"),
+    ];
+    harness.start_with_env(&[], &env);
+    harness.expect_output("Server active");
+    harness.wait_for_file(&received, |messages| messages.contains("config"));
+
+    // the game still gets them, in its file
+    let file = harness.dir.join("data/credentials.cmdline.txt");
+    assert!(fs::read_to_string(file).unwrap().contains("hunter2"));
+    let plugin_env = harness.wait_for_file(&env_file, |env| !env.is_empty());
+    assert!(!plugin_env.contains("hunter2"), "{}", plugin_env);
+    assert!(!plugin_env.contains("BRIXIDE_EMAIL"), "{}", plugin_env);
+    let build_env = harness
+        .wait_for_file(&harness.dir.join("plugins/builder/build_env.txt"), |env| {
+            !env.is_empty()
+        });
+    assert!(!build_env.contains("hunter2"), "{}", build_env);
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn reads_credentials_from_stdin() {
+    let mut harness = Harness::new("reads_credentials_from_stdin");
+    fs::remove_dir_all(harness.dir.join("data/Saved/Auth")).unwrap();
+
+    harness.start_with(&["--credentials-stdin"]);
+    harness.console("host@example.com");
+    harness.console("hunter2");
+    harness.expect_output("Server active");
+    let file = harness.dir.join("data/credentials.cmdline.txt");
+    assert_eq!(
+        fs::read_to_string(file).unwrap(),
+        "-User=\"host@example.com\" -Password=\"hunter2\""
+    );
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
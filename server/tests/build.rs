@@ -0,0 +1,130 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::Harness;
+
+const PLUGIN: &str = "while read line; do\n\
+                      case \"$line\" in *shutdown*) exit 0;; esac\n\
+                      done\n";
+
+#[test]
+fn builds_plugins_before_starting_them() {
+    let mut harness = Harness::new("builds_plugins_before_starting_them");
+    for id in ["built", "broken"].iter() {
+        harness.plugin_with(id, Path::new("run.sh"), "");
+        fs::write(
+            harness.dir.join("plugins").join(id).join("src.sh"),
+            format!("#!/bin/sh\n{}", PLUGIN),
+        )
+        .unwrap();
+    }
+    let manifest = harness.dir.join("plugins/built/plugin.toml");
+    let mut contents = fs::read_to_string(&manifest).unwrap();
+    contents.push_str("build = \"echo compiling && cp src.sh run.sh && chmod +x run.sh\"\n");
+    fs::write(&manifest, contents).unwrap();
+    let manifest = harness.dir.join("plugins/broken/plugin.toml");
+    let mut contents = fs::read_to_string(&manifest).unwrap();
+    contents.push_str("build = \"echo syntax error >&2; exit 1\"\n");
+    fs::write(&manifest, contents).unwrap();
+
+    harness.start();
+    harness.expect_output("Started 1 plugins");
+
+    let output = harness.output();
+    assert!(output.contains("Building plugin built"), "{}", output);
+    assert!(
+        output.contains("Plugin broken failed to build: the build command failed"),
+        "{}",
+        output
+    );
+    assert!(harness.dir.join("plugins/built/run.sh").exists());
+
+    let log = fs::read_to_string(harness.dir.join("data/logs/plugins/built.log")).unwrap();
+    assert!(log.contains("compiling"), "{}", log);
+    let log = fs::read_to_string(harness.dir.join("data/logs/plugins/broken.log")).unwrap();
+    assert!(log.contains("syntax error"), "{}", log);
+
+    // an up-to-date target isn't rebuilt, but the subcommand builds it anyway
+    let (status, output) = harness.run(&["plugin", "build", "built"]);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("Built plugin built"), "{}", output);
+    let (status, output) = harness.run(&["plugin", "build"]);
+    assert_eq!(status.code(), Some(1), "{}", output);
+}
+
+#[test]
+fn builds_off_the_main_loop_when_reloading() {
+    let mut harness = Harness::new("builds_off_the_main_loop_when_reloading");
+    harness.plugin_with(
+        "slow",
+        Path::new("run.sh"),
+        "build = \"sleep 2 && cp src.sh run.sh && chmod +x run.sh\"\n",
+    );
+    let source = harness.dir.join("plugins/slow/src.sh");
+    fs::write(&source, format!("#!/bin/sh\n{}", PLUGIN)).unwrap();
+    harness.start();
+    harness.expect_output("Started 1 plugins");
+
+    // the changed source needs building again, and the console answers while that happens
+    fs::write(&source, format!("#!/bin/sh\n# changed\n{}", PLUGIN)).unwrap();
+    harness.console(".reload slow");
+    harness.wait_for_file(&harness.dir.join("wrapper.log"), |output| {
+        output.matches("Building plugin slow").count() == 2
+    });
+    harness.console(".players");
+    harness.expect_output("0 players online");
+    harness.expect_output("Reloaded plugin slow");
+    let output = harness.output();
+    assert!(
+        output.find("0 players online").unwrap() < output.find("Reloaded plugin slow").unwrap(),
+        "{}",
+        output
+    );
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn ignores_files_plugins_write_into_their_folder() {
+    let mut harness = Harness::new("ignores_files_plugins_write_into_their_folder");
+    // one lists its sources, the other writes where build output goes
+    for (id, writes, extra) in [
+        ("listed", "state.txt", "sources = [\"src.sh\"]\n"),
+        ("unlisted", "dist/state.txt", ""),
+    ] {
+        harness.plugin_with(
+            id,
+            Path::new("run.sh"),
+            &format!("build = \"cp src.sh run.sh && chmod +x run.sh\"\n{}", extra),
+        );
+        fs::write(
+            harness.dir.join("plugins").join(id).join("src.sh"),
+            format!(
+                "#!/bin/sh\nmkdir -p dist && sleep 1 && echo written > {}\n{}",
+                writes, PLUGIN
+            ),
+        )
+        .unwrap();
+    }
+
+    harness.start();
+    harness.expect_output("Started 2 plugins");
+    harness.wait_for_file(&harness.dir.join("plugins/listed/state.txt"), |s| {
+        !s.is_empty()
+    });
+    harness.wait_for_file(&harness.dir.join("plugins/unlisted/dist/state.txt"), |s| {
+        !s.is_empty()
+    });
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+
+    harness.start();
+    harness.expect_output("Started 2 plugins");
+    let output = harness.output();
+    assert!(!output.contains("Building plugin"), "{}", output);
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use std::fs;
+
+use common::Harness;
+
+#[test]
+fn checks_plugin_manifests() {
+    let harness = Harness::new("checks_plugin_manifests");
+    let target = harness.shell_script("good.sh", "exit 0\n");
+    harness.plugin_with("good", &target, "");
+    harness.plugin_with(
+        "typo",
+        &target,
+        "depend = [\"good\"]\n\
+         [config.greeting]\n\
+         type = \"string\"\n\
+         descripton = \"What to greet players with\"\n",
+    );
+    harness.plugin_with("missing", &harness.dir.join("nowhere.sh"), "");
+    fs::write(harness.dir.join("not_executable.sh"), "exit 0\n").unwrap();
+    harness.plugin_with("stuck", &harness.dir.join("not_executable.sh"), "");
+    fs::create_dir_all(harness.dir.join("plugins/broken")).unwrap();
+    fs::write(
+        harness.dir.join("plugins/broken/plugin.toml"),
+        "[plugin]\nname = \"broken\"\nauthor = \"test\"\ndescription = \"test\"\nrestart = \"sometimes\"\n",
+    )
+    .unwrap();
+
+    let (status, output) = harness.run(&["plugin", "check"]);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(output.contains("plugins/good is ok"), "{}", output);
+    assert!(
+        output.contains("plugins/typo: unknown key plugin.depend"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("plugins/typo: unknown key config.greeting.descripton"),
+        "{}",
+        output
+    );
+    assert!(output.contains("plugins/typo is ok"), "{}", output);
+    assert!(output.contains("nowhere.sh doesn't exist"), "{}", output);
+    assert!(
+        output.contains("not_executable.sh isn't executable"),
+        "{}",
+        output
+    );
+    assert!(output.contains("unknown variant `sometimes`"), "{}", output);
+    assert!(output.contains("line 5, column 11"), "{}", output);
+
+    let (status, output) = harness.run(&["plugin", "check", "plugins/good"]);
+    assert!(status.success(), "{}", output);
+}
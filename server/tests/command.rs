@@ -0,0 +1,47 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::Harness;
+
+#[test]
+fn runs_plugins_through_an_interpreter() {
+    let mut harness = Harness::new("runs_plugins_through_an_interpreter");
+    harness.plugin_with(
+        "scripted",
+        Path::new("unused"),
+        "command = \"sh\"\n\
+         args = [\"main.sh\", \"from args\"]\n\
+         cwd = \"work\"\n\
+         [plugin.env]\n\
+         GREETING = \"from env\"\n",
+    );
+    let dir = harness.dir.join("plugins/scripted");
+    fs::create_dir_all(dir.join("work")).unwrap();
+    // not executable, as the interpreter runs it; it's found relative to the cwd
+    fs::write(
+        dir.join("work/main.sh"),
+        "echo \"$1, $GREETING, $(pwd)\" > started.txt\n\
+         while read line; do\n\
+         case \"$line\" in *shutdown*) exit 0;; esac\n\
+         done\n",
+    )
+    .unwrap();
+
+    let (status, output) = harness.run(&["plugin", "check", "plugins/scripted"]);
+    assert!(status.success(), "{}", output);
+
+    harness.start();
+    harness.expect_output("Started 1 plugins");
+
+    let started = dir.join("work/started.txt");
+    let line = harness.wait_for_file(&started, |line| !line.is_empty());
+    let cwd = fs::canonicalize(dir.join("work")).unwrap();
+    assert_eq!(
+        line.trim(),
+        format!("from args, from env, {}", cwd.display())
+    );
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
@@ -0,0 +1,349 @@
+//! Helpers for running the wrapper against `fake_brickadia` in a scratch directory.
+
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::Once,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+const WRAPPER: &str = env!("CARGO_BIN_EXE_server");
+const FAKE_GAME: &str = env!("CARGO_BIN_EXE_fake_brickadia");
+const TARGET_TMPDIR: &str = env!("CARGO_TARGET_TMPDIR");
+
+static BUILD_PING_PONG: Once = Once::new();
+
+/// Builds `plugins/ping_pong_plugin` (once per test binary) and returns the path to its binary.
+pub fn ping_pong_plugin() -> PathBuf {
+    let target_dir = Path::new(TARGET_TMPDIR).join("ping_pong_plugin");
+
+    BUILD_PING_PONG.call_once(|| {
+        let manifest =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../plugins/ping_pong_plugin/Cargo.toml");
+        let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+            .arg("build")
+            .arg("--manifest-path")
+            .arg(manifest)
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .status()
+            .expect("Failed to run cargo");
+        assert!(status.success(), "Failed to build ping_pong_plugin");
+    });
+
+    target_dir.join("debug/ping_pong_plugin")
+}
+
+pub const ALL_CAPABILITIES: &[&str] =
+    &["console", "broadcast", "moderation", "storage", "messaging"];
+
+/// A `capabilities = [...]` line for plugin.toml or approved.toml.
+pub fn capabilities(capabilities: &[&str]) -> String {
+    let quoted: Vec<String> = capabilities.iter().map(|c| format!("\"{}\"", c)).collect();
+    format!("capabilities = [{}]\n", quoted.join(", "))
+}
+
+/// A wrapper process running in its own directory, with `fake_brickadia` as its game.
+pub struct Harness {
+    pub dir: PathBuf,
+    wrapper: Option<Child>,
+}
+
+impl Harness {
+    /// Creates an empty working directory for the test, with server auth already in place.
+    pub fn new(name: &str) -> Self {
+        let dir = Path::new(TARGET_TMPDIR).join("e2e").join(name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("data/Saved/Auth")).unwrap();
+        fs::create_dir_all(dir.join("plugins")).unwrap();
+
+        Harness { dir, wrapper: None }
+    }
+
+    /// Installs a plugin under `plugins/<id>` whose target is the given binary, with every
+    /// capability declared and approved.
+    pub fn plugin(&self, id: &str, target: &Path) {
+        self.plugin_with(id, target, &capabilities(ALL_CAPABILITIES));
+        self.approve(id, ALL_CAPABILITIES);
+    }
+
+    /// Approves capabilities for a plugin, as the operator would.
+    pub fn approve(&self, id: &str, capabilities: &[&str]) {
+        let path = self.dir.join("data/plugins").join(id);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("approved.toml"), self::capabilities(capabilities)).unwrap();
+    }
+
+    /// Installs a plugin with extra lines in the `[plugin]` table of its manifest.
+    pub fn plugin_with(&self, id: &str, target: &Path, extra: &str) {
+        let path = self.dir.join("plugins").join(id);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(
+            path.join("plugin.toml"),
+            format!(
+                "[plugin]\nname = \"{}\"\nauthor = \"test\"\ndescription = \"test\"\ntarget = \"{}\"\n{}",
+                id,
+                target.display(),
+                extra
+            ),
+        )
+        .unwrap();
+    }
+
+    /// Writes an executable shell script into the test directory, for use as a plugin target.
+    pub fn shell_script(&self, name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = self.dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Writes a plugin script that runs the shell commands in `extra`, then appends every line it
+    /// receives to the file at [`Harness::received`] until it's told to shut down.
+    pub fn recording_script(&self, id: &str, extra: &str) -> PathBuf {
+        self.shell_script(
+            &format!("{}.sh", id),
+            &format!(
+                "{}while read line; do\n\
+                 echo \"$line\" >> {}\n\
+                 case \"$line\" in *shutdown*) exit 0;; esac\n\
+                 done\n",
+                extra,
+                self.received(id).display()
+            ),
+        )
+    }
+
+    /// Installs a recording plugin (see [`Harness::recording_script`]) with every capability,
+    /// returning the file it records to.
+    pub fn recording_plugin(&self, id: &str, extra: &str) -> PathBuf {
+        let target = self.recording_script(id, extra);
+        self.plugin(id, &target);
+        self.received(id)
+    }
+
+    /// The file a recording plugin writes the lines it receives to.
+    pub fn received(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.received.txt", id))
+    }
+
+    /// Waits until the file at `path` has contents matching `predicate`, returning them. Panics
+    /// after a timeout.
+    pub fn wait_for_file(&self, path: &Path, predicate: impl Fn(&str) -> bool) -> String {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(30) {
+            let contents = fs::read_to_string(path).unwrap_or_default();
+            if predicate(&contents) {
+                return contents;
+            }
+            sleep(Duration::from_millis(50));
+        }
+
+        panic!(
+            "{} never had the expected contents:\n{}\nwrapper output:\n{}",
+            path.display(),
+            fs::read_to_string(path).unwrap_or_default(),
+            self.output()
+        );
+    }
+
+    /// Writes the script the fake game plays back once it starts.
+    pub fn script(&self, script: &str) {
+        fs::write(self.dir.join("script.txt"), script).unwrap();
+    }
+
+    /// Starts the wrapper.
+    pub fn start(&mut self) {
+        self.start_with(&[]);
+    }
+
+    /// Starts the wrapper with extra command line arguments.
+    pub fn start_with(&mut self, args: &[&str]) {
+        self.start_with_env(args, &[]);
+    }
+
+    /// Starts the wrapper with extra command line arguments and environment variables.
+    pub fn start_with_env(&mut self, args: &[&str], env: &[(&str, &str)]) {
+        let game_command = self.game_command(
+            &self.record_path().display().to_string(),
+            &self.dir.join("args.txt").display().to_string(),
+        );
+        self.spawn(&game_command, args, env);
+    }
+
+    /// The command running `fake_brickadia` with the test's script, recording its stdin and its
+    /// arguments to the given files.
+    pub fn game_command(&self, record: &str, record_args: &str) -> String {
+        format!(
+            "{} --script {} --record {} --record-args {}",
+            FAKE_GAME,
+            self.dir.join("script.txt").display(),
+            record,
+            record_args
+        )
+    }
+
+    /// Starts the wrapper with the given game command and extra command line arguments.
+    pub fn start_game(&mut self, game_command: &str, args: &[&str]) {
+        self.spawn(game_command, args, &[]);
+    }
+
+    fn spawn(&mut self, game_command: &str, args: &[&str], env: &[(&str, &str)]) {
+        if !self.dir.join("script.txt").exists() {
+            self.script("");
+        }
+
+        let log = fs::File::create(self.dir.join("wrapper.log")).unwrap();
+        let wrapper = Command::new(WRAPPER)
+            .current_dir(&self.dir)
+            .arg("--game-command")
+            .arg(game_command)
+            .args(args)
+            .envs(env.iter().copied())
+            .stdin(Stdio::piped())
+            .stdout(log.try_clone().unwrap())
+            .stderr(log)
+            .spawn()
+            .expect("Failed to start the wrapper");
+
+        self.wrapper = Some(wrapper);
+    }
+
+    /// Runs one of the wrapper's subcommands to completion, returning its exit status and output.
+    pub fn run(&self, args: &[&str]) -> (ExitStatus, String) {
+        self.run_with_env(args, &[])
+    }
+
+    /// Runs one of the wrapper's subcommands with extra environment variables.
+    pub fn run_with_env(&self, args: &[&str], env: &[(&str, &str)]) -> (ExitStatus, String) {
+        let output = Command::new(WRAPPER)
+            .current_dir(&self.dir)
+            .args(args)
+            .envs(env.iter().copied())
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run the wrapper");
+
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        (output.status, text)
+    }
+
+    /// Types a line into the wrapper's console.
+    pub fn console(&mut self, line: &str) {
+        let stdin = self
+            .wrapper
+            .as_mut()
+            .and_then(|wrapper| wrapper.stdin.as_mut())
+            .expect("The wrapper isn't running");
+        writeln!(stdin, "{}", line).unwrap();
+        stdin.flush().unwrap();
+    }
+
+    /// Sends a signal such as `TERM` to the wrapper.
+    pub fn signal(&self, signal: &str) {
+        let wrapper = self.wrapper.as_ref().expect("The wrapper isn't running");
+        let status = Command::new("kill")
+            .arg(format!("-{}", signal))
+            .arg(wrapper.id().to_string())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// Waits for the wrapper to exit by itself, panicking after a timeout.
+    pub fn wait_for_exit(&mut self) -> ExitStatus {
+        let wrapper = self.wrapper.as_mut().expect("The wrapper isn't running");
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(30) {
+            if let Some(status) = wrapper.try_wait().unwrap() {
+                return status;
+            }
+            sleep(Duration::from_millis(50));
+        }
+
+        panic!(
+            "the wrapper never exited\nwrapper output:\n{}",
+            self.output()
+        );
+    }
+
+    /// Waits until the wrapper has logged something containing `text`, panicking after a timeout.
+    pub fn expect_output(&self, text: &str) {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(30) {
+            if self.output().contains(text) {
+                return;
+            }
+            sleep(Duration::from_millis(50));
+        }
+
+        panic!(
+            "expected {:?} in the wrapper output:\n{}",
+            text,
+            self.output()
+        );
+    }
+
+    fn record_path(&self) -> PathBuf {
+        self.dir.join("stdin.txt")
+    }
+
+    /// Every line the wrapper has written to the game's stdin so far.
+    pub fn recorded(&self) -> Vec<String> {
+        fs::read_to_string(self.record_path())
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    /// The arguments the wrapper last started the game with.
+    pub fn game_args(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.join("args.txt"))
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    /// Everything the wrapper has logged so far.
+    pub fn output(&self) -> String {
+        fs::read_to_string(self.dir.join("wrapper.log")).unwrap_or_default()
+    }
+
+    /// Waits until the game has received a line matching `predicate`, panicking after a timeout.
+    pub fn expect_recorded(&self, predicate: impl Fn(&str) -> bool) -> String {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(30) {
+            if let Some(line) = self.recorded().into_iter().find(|l| predicate(l)) {
+                return line;
+            }
+            sleep(Duration::from_millis(50));
+        }
+
+        panic!(
+            "expected line was never written to the game\nrecorded: {:#?}\nwrapper output:\n{}",
+            self.recorded(),
+            self.output()
+        );
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        if let Some(mut wrapper) = self.wrapper.take() {
+            let _ = wrapper.kill();
+            let _ = wrapper.wait();
+        }
+    }
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::Harness;
+
+const SCHEMA: &str = "\n\
+    [config.greeting]\n\
+    type = \"string\"\n\
+    default = \"Hello\"\n\
+    \n\
+    [config.max_pings]\n\
+    type = \"integer\"\n\
+    default = 3\n\
+    \n\
+    [config.ratio]\n\
+    type = \"float\"\n\
+    default = 0.5\n";
+
+/// Installs a plugin that asks for its config and records every message it gets.
+fn recording_plugin(harness: &Harness) -> PathBuf {
+    let target = harness.recording_script(
+        "recorder",
+        "echo '{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"config.get\"}'\n",
+    );
+    harness.plugin_with("recorder", &target, SCHEMA);
+    harness.received("recorder")
+}
+
+#[test]
+fn delivers_config_with_overrides() {
+    let mut harness = Harness::new("delivers_config_with_overrides");
+    let received = recording_plugin(&harness);
+    let data_dir = harness.dir.join("data/plugins/recorder");
+    fs::create_dir_all(&data_dir).unwrap();
+    fs::write(data_dir.join("config.toml"), "max_pings = 5\nratio = 1\n").unwrap();
+    harness.start();
+
+    let config = r#"{"greeting":"Hello","max_pings":5,"ratio":1.0}"#;
+    let messages = harness.wait_for_file(&received, |messages| messages.lines().count() >= 2);
+    let lines: Vec<&str> = messages.lines().collect();
+
+    // sent at startup, then in response to config.get
+    assert!(lines[0].contains(r#""method":"config""#), "{}", lines[0]);
+    assert!(lines[0].contains(config), "{}", lines[0]);
+    assert!(lines[1].contains(r#""id":1"#), "{}", lines[1]);
+    assert!(lines[1].contains(config), "{}", lines[1]);
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn refuses_invalid_overrides() {
+    let mut harness = Harness::new("refuses_invalid_overrides");
+    recording_plugin(&harness);
+    let data_dir = harness.dir.join("data/plugins/recorder");
+    fs::create_dir_all(&data_dir).unwrap();
+    fs::write(
+        data_dir.join("config.toml"),
+        "max_pings = \"lots\"\nvolume = 11\n",
+    )
+    .unwrap();
+    harness.start();
+
+    harness.expect_output("Started 0 plugins");
+    let output = harness.output();
+    assert!(
+        output.contains("max_pings should be an integer (from the override)"),
+        "{}",
+        output
+    );
+    assert!(output.contains("unknown option volume"), "{}", output);
+
+    harness.signal("TERM");
+    harness.wait_for_exit();
+}
+
+#[test]
+fn writes_a_config_template() {
+    let mut harness = Harness::new("writes_a_config_template");
+    recording_plugin(&harness);
+    harness.start();
+    harness.expect_output("Started 1 plugins");
+
+    let template =
+        fs::read_to_string(harness.dir.join("data/plugins/recorder/config.toml")).unwrap();
+    assert!(template.contains("# greeting = \"Hello\""), "{}", template);
+    assert!(template.contains("# max_pings = 3"), "{}", template);
+
+    harness.signal("TERM");
+    harness.wait_for_exit();
+}
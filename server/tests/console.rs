@@ -0,0 +1,28 @@
+mod common;
+
+use common::{ping_pong_plugin, Harness};
+
+#[test]
+fn forwards_lines_and_runs_wrapper_commands() {
+    let mut harness = Harness::new("forwards_lines_and_runs_wrapper_commands");
+    harness.plugin("ping_pong_plugin", &ping_pong_plugin());
+    harness.script(
+        "wait 500\n\
+         join Alice 5b0e1b1e-1d5c-4e4a-a4f5-ef6a7a1b2c3d\n",
+    );
+    harness.start();
+    harness.expect_recorded(|line| line.contains("is connecting"));
+
+    harness.console("Server.Status");
+    harness.expect_recorded(|line| line == "Server.Status");
+
+    harness.console(".players");
+    harness.expect_output("Alice (5b0e1b1e-1d5c-4e4a-a4f5-ef6a7a1b2c3d)");
+
+    harness.console(".reload ping_pong_plugin");
+    harness.expect_output("Reloaded plugin ping_pong_plugin");
+
+    harness.console(".stop");
+    assert!(harness.wait_for_exit().success());
+    assert!(harness.recorded().iter().any(|line| line == "exit"));
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::Harness;
+
+#[test]
+fn starts_plugins_in_dependency_order() {
+    let mut harness = Harness::new("starts_plugins_in_dependency_order");
+    let plugins = [
+        ("addon", "depends = [\"base\"]\n"),
+        ("base", ""),
+        ("cycle_a", "depends = [\"cycle_b\"]\n"),
+        ("cycle_b", "depends = [\"cycle_a\"]\n"),
+        ("extra", "optional_depends = [\"addon\", \"absent\"]\n"),
+        ("orphan", "depends = [\"missing\"]\n"),
+    ];
+    for (id, extra) in plugins.iter() {
+        let target = harness.recording_script(id, "");
+        harness.plugin_with(id, &target, extra);
+    }
+    harness.start();
+    harness.expect_output("Started 3 plugins");
+
+    let output = harness.output();
+    assert!(
+        output.contains("Not starting plugin orphan: it depends on missing, which isn't available"),
+        "{}",
+        output
+    );
+    assert!(output.contains("Not starting plugin cycle_a: its dependencies are cyclic"));
+    assert!(output.contains("Not starting plugin cycle_b: its dependencies are cyclic"));
+
+    // base hears about every plugin started after it
+    let messages = harness.wait_for_file(&harness.received("base"), |messages| {
+        messages.contains("\"id\":\"extra\"")
+    });
+    let order: Vec<&str> = messages
+        .lines()
+        .filter(|line| line.contains("plugin.started"))
+        .map(|line| {
+            if line.contains("\"id\":\"addon\"") {
+                "addon"
+            } else {
+                "extra"
+            }
+        })
+        .collect();
+    assert_eq!(order, ["addon", "extra"], "{}", messages);
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn restarts_dependents_with_their_dependency() {
+    let mut harness = Harness::new("restarts_dependents_with_their_dependency");
+    for (id, extra) in [
+        ("addon", "depends = [\"base\"]\n"),
+        ("base", ""),
+        ("other", ""),
+    ] {
+        let target = harness.recording_script(id, "");
+        harness.plugin_with(id, &target, extra);
+    }
+    harness.start();
+    harness.expect_output("Started 3 plugins");
+
+    harness.console(".reload base");
+    harness.expect_output("Reloaded plugin base");
+    harness.expect_output("Restarted plugin addon");
+
+    // the dependent stops first and starts last
+    let messages = harness.wait_for_file(&harness.received("other"), |messages| {
+        messages.matches("plugin.started").count() >= 2
+    });
+    let events: Vec<String> = messages
+        .lines()
+        .filter_map(|line| {
+            let event = if line.contains("plugin.started") {
+                "started"
+            } else if line.contains("plugin.stopped") {
+                "stopped"
+            } else {
+                return None;
+            };
+            let id = ["addon", "base"]
+                .iter()
+                .find(|id| line.contains(&format!("\"id\":\"{}\"", id)))?;
+            Some(format!("{} {}", event, id))
+        })
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "stopped addon",
+            "stopped base",
+            "started base",
+            "started addon"
+        ],
+        "{}",
+        messages
+    );
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::fs;
+
+use common::Harness;
+
+const EXISTING: &str = "[Server__BP_ServerSettings_General_C BP_ServerSettings_General]\n\
+                        MaxSelectedBricks=1000\n\
+                        servername=Old name\n\
+                        \n\
+                        [Other]\n\
+                        Kept=yes\n";
+
+#[test]
+fn writes_server_settings_for_the_game() {
+    let mut harness = Harness::new("writes_server_settings_for_the_game");
+    let settings = harness
+        .dir
+        .join("data/Saved/Config/LinuxServer/ServerSettings.ini");
+    fs::create_dir_all(settings.parent().unwrap()).unwrap();
+    fs::write(&settings, EXISTING).unwrap();
+    fs::write(
+        harness.dir.join("brixide.toml"),
+        "port = 7788\n\
+         [server]\n\
+         name = \"Test server\"\n\
+         max_players = 20\n\
+         public = false\n\
+         [game]\n\
+         args = [\"-Extra\"]\n",
+    )
+    .unwrap();
+
+    harness.start();
+    harness.expect_output("Server active");
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+
+    assert_eq!(
+        fs::read_to_string(&settings).unwrap(),
+        "[Server__BP_ServerSettings_General_C BP_ServerSettings_General]\n\
+         MaxSelectedBricks=1000\n\
+         ServerName=Test server\n\
+         MaxPlayers=20\n\
+         bPubliclyListed=False\n\
+         \n\
+         [Other]\n\
+         Kept=yes\n"
+    );
+    let args = harness.game_args();
+    assert!(args.contains(&"-port=7788".to_owned()), "{:?}", args);
+    assert!(args.contains(&"-Extra".to_owned()), "{:?}", args);
+
+    // a fresh install gets a settings file of its own
+    fs::remove_file(&settings).unwrap();
+    harness.start();
+    harness.expect_output("Server active");
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+    assert_eq!(
+        fs::read_to_string(&settings).unwrap(),
+        "[Server__BP_ServerSettings_General_C BP_ServerSettings_General]\n\
+         ServerName=Test server\n\
+         MaxPlayers=20\n\
+         bPubliclyListed=False\n"
+    );
+}
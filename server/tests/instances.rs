@@ -0,0 +1,101 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::Harness;
+
+#[test]
+fn runs_several_instances() {
+    let mut harness = Harness::new("runs_several_instances");
+    fs::write(
+        harness.dir.join("brixide.toml"),
+        "[server]\n\
+         name = \"Shared\"\n\
+         [instances.alpha]\n\
+         port = 7781\n\
+         [instances.beta]\n\
+         port = 7782\n\
+         args = [\"-Beta\"]\n\
+         server.name = \"Beta\"\n",
+    )
+    .unwrap();
+    for name in ["alpha", "beta"] {
+        fs::create_dir_all(
+            harness
+                .dir
+                .join("data/instances")
+                .join(name)
+                .join("Saved/Auth"),
+        )
+        .unwrap();
+    }
+
+    let game_command = harness.game_command("{user_dir}/stdin.txt", "{user_dir}/args.txt");
+    harness.start_game(&game_command, &[]);
+    harness.expect_output("[alpha] Server active");
+    harness.expect_output("[beta] Server active");
+
+    let alpha = harness.dir.join("data/instances/alpha");
+    let beta = harness.dir.join("data/instances/beta");
+    let args = harness.wait_for_file(&alpha.join("args.txt"), |args| !args.is_empty());
+    assert!(args.lines().any(|arg| arg == "-port=7781"), "{}", args);
+    let args = harness.wait_for_file(&beta.join("args.txt"), |args| !args.is_empty());
+    assert!(args.lines().any(|arg| arg == "-port=7782"), "{}", args);
+    assert!(args.lines().any(|arg| arg == "-Beta"), "{}", args);
+
+    // lines go to the first instance unless they're addressed to another
+    harness.console("Chat.Broadcast \"to alpha\"");
+    harness.console("@beta Chat.Broadcast \"to beta\"");
+    harness.console(".use beta");
+    harness.console("Chat.Broadcast \"to beta again\"");
+    let beta_lines =
+        harness.wait_for_file(&beta.join("stdin.txt"), |lines| lines.lines().count() >= 2);
+    assert_eq!(
+        beta_lines.lines().collect::<Vec<_>>(),
+        vec![
+            "Chat.Broadcast \"to beta\"",
+            "Chat.Broadcast \"to beta again\""
+        ]
+    );
+    let alpha_lines = harness.wait_for_file(&alpha.join("stdin.txt"), |lines| !lines.is_empty());
+    assert_eq!(alpha_lines, "Chat.Broadcast \"to alpha\"\n");
+
+    // each instance gets its own server settings, falling back to the shared ones
+    let settings = |dir: &Path| {
+        fs::read_to_string(dir.join("Saved/Config/LinuxServer/ServerSettings.ini")).unwrap()
+    };
+    assert!(settings(&alpha).contains("ServerName=Shared"));
+    assert!(settings(&beta).contains("ServerName=Beta"));
+
+    // stopping one leaves the other running, and the wrapper exits once both have stopped
+    harness.console("@alpha .stop");
+    harness.expect_output("Instance alpha stopped");
+    assert!(!harness.output().contains("Instance beta stopped"));
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+    harness.expect_output("Instance beta stopped");
+}
+
+#[test]
+fn refuses_instances_sharing_a_port() {
+    let mut harness = Harness::new("refuses_instances_sharing_a_port");
+    fs::write(
+        harness.dir.join("brixide.toml"),
+        "[instances.alpha]\n\
+         [instances.beta]\n",
+    )
+    .unwrap();
+
+    harness.start();
+    assert_eq!(harness.wait_for_exit().code(), Some(1));
+    harness.expect_output("instances alpha and beta both use port 7777");
+}
+
+#[test]
+fn exits_when_the_game_cannot_start() {
+    let mut harness = Harness::new("exits_when_the_game_cannot_start");
+
+    harness.start_game("/nonexistent/game", &[]);
+    assert_eq!(harness.wait_for_exit().code(), Some(1));
+    harness.expect_output("Failed to start the game (/nonexistent/game)");
+}
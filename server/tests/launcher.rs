@@ -0,0 +1,341 @@
+mod common;
+
+use std::{
+    fs,
+    io::{Read, Write},
+    net::TcpListener,
+    path::Path,
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use common::Harness;
+
+/// What a request to [`serve`] asked for: where to resume from, and the `If-Range` it sent.
+type Requested = (Option<u64>, Option<String>);
+type Shared<T> = Arc<Mutex<T>>;
+
+/// The archive a [`serve`] server hands out, with its ETag, and whether to cut the next response
+/// off halfway.
+struct Served {
+    body: Vec<u8>,
+    etag: String,
+    cut_next: bool,
+}
+
+/// Serves an archive on a local port, honouring `Range: bytes=<start>-` headers when their
+/// `If-Range` matches the archive's ETag. Returns the URL to download it from, the archive to
+/// change what's served, and the requests so far.
+fn serve(body: Vec<u8>, etag: &str) -> (String, Shared<Served>, Shared<Vec<Requested>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/brickadia-launcher.tar.xz",
+        listener.local_addr().unwrap()
+    );
+    let served = Arc::new(Mutex::new(Served {
+        body,
+        etag: etag.into(),
+        cut_next: false,
+    }));
+    let requests = Arc::new(Mutex::new(vec![]));
+
+    let (archive, requested) = (served.clone(), requests.clone());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut request = [0; 4096];
+            let len = stream.read(&mut request).unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..len]).into_owned();
+            let header = |name: &str| {
+                request.lines().find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    key.eq_ignore_ascii_case(name)
+                        .then(|| value.trim().to_owned())
+                })
+            };
+            let start = header("range").and_then(|range| {
+                range
+                    .strip_prefix("bytes=")?
+                    .trim_end_matches('-')
+                    .parse::<u64>()
+                    .ok()
+            });
+            let if_range = header("if-range");
+            requested.lock().unwrap().push((start, if_range.clone()));
+
+            let mut archive = archive.lock().unwrap();
+            let cut = std::mem::take(&mut archive.cut_next);
+            let body = &archive.body;
+            let (head, rest) = match start {
+                Some(start) if if_range.as_ref() == Some(&archive.etag) => (
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\
+                         Content-Range: bytes {}-{}/{}\r\n",
+                        body.len() - start as usize,
+                        start,
+                        body.len() - 1,
+                        body.len()
+                    ),
+                    &body[start as usize..],
+                ),
+                // the archive changed, so it's sent whole
+                _ => (
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", body.len()),
+                    &body[..],
+                ),
+            };
+            let rest = if cut { &rest[..rest.len() / 2] } else { rest };
+            let _ = write!(
+                stream,
+                "{}ETag: {}\r\nConnection: close\r\n\r\n",
+                head, archive.etag
+            )
+            .and_then(|()| stream.write_all(rest));
+        }
+    });
+
+    (url, served, requests)
+}
+
+/// The names of the partial downloads in the data folder.
+fn partial_downloads(harness: &Harness) -> Vec<String> {
+    fs::read_dir(harness.dir.join("data"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".part"))
+        .collect()
+}
+
+/// Packs a fake launcher into a .tar.xz, returning the archive and its SHA-256.
+fn launcher_archive(dir: &Path, contents: &str) -> (Vec<u8>, String) {
+    let source = dir.join("archive");
+    fs::create_dir_all(source.join("brickadia-launcher")).unwrap();
+    fs::write(
+        source.join("brickadia-launcher/main-brickadia-launcher"),
+        contents,
+    )
+    .unwrap();
+
+    let archive = dir.join("archive.tar.xz");
+    let status = Command::new("tar")
+        .arg("cJf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&source)
+        .arg("brickadia-launcher")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = Command::new("sha256sum").arg(&archive).output().unwrap();
+    let sha256 = String::from_utf8(output.stdout).unwrap()[..64].to_owned();
+    (fs::read(&archive).unwrap(), sha256)
+}
+
+#[test]
+fn installs_only_verified_launchers() {
+    let harness = Harness::new("installs_only_verified_launchers");
+    let (archive, sha256) = launcher_archive(&harness.dir, "first");
+    let (url, _, _) = serve(archive, "\"first\"");
+    let launcher = harness
+        .dir
+        .join("data/brickadia-launcher/main-brickadia-launcher");
+
+    let (status, output) = harness.run_with_env(
+        &["install"],
+        &[
+            ("BRIXIDE_LAUNCHER_VERSION", "9.9"),
+            ("BRIXIDE_LAUNCHER_URL", &url),
+            ("BRIXIDE_LAUNCHER_SHA256", &sha256),
+        ],
+    );
+    assert!(status.success(), "{}", output);
+    assert!(
+        output.contains("Verified the launcher archive"),
+        "{}",
+        output
+    );
+    assert_eq!(fs::read_to_string(&launcher).unwrap(), "first");
+    let marker = fs::read_to_string(harness.dir.join("data/launcher.toml")).unwrap();
+    assert!(marker.contains("version = \"9.9\""), "{}", marker);
+    assert!(marker.contains(&sha256), "{}", marker);
+
+    // an archive that doesn't match its digest doesn't replace the working launcher
+    let (status, output) = harness.run_with_env(
+        &["install"],
+        &[
+            ("BRIXIDE_LAUNCHER_VERSION", "9.9"),
+            ("BRIXIDE_LAUNCHER_URL", &url),
+            ("BRIXIDE_LAUNCHER_SHA256", &"0".repeat(64)),
+        ],
+    );
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(output.contains("wasn't installed"), "{}", output);
+    assert_eq!(fs::read_to_string(&launcher).unwrap(), "first");
+
+    // nor does one that can't be verified at all, which is kept for when its digest is set
+    let (status, output) = harness.run_with_env(
+        &["install"],
+        &[
+            ("BRIXIDE_LAUNCHER_VERSION", "9.9"),
+            ("BRIXIDE_LAUNCHER_URL", &url),
+        ],
+    );
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(output.contains("no known SHA-256"), "{}", output);
+    assert_eq!(fs::read_to_string(&launcher).unwrap(), "first");
+    assert_eq!(partial_downloads(&harness).len(), 1);
+}
+
+#[test]
+fn installs_unverified_launchers_only_when_allowed() {
+    let harness = Harness::new("installs_unverified_launchers_only_when_allowed");
+    let (archive, _) = launcher_archive(&harness.dir, "unverified");
+    let (url, _, _) = serve(archive, "\"unverified\"");
+    let launcher = harness
+        .dir
+        .join("data/brickadia-launcher/main-brickadia-launcher");
+    let env = [
+        ("BRIXIDE_LAUNCHER_VERSION", "9.9"),
+        ("BRIXIDE_LAUNCHER_URL", url.as_str()),
+    ];
+
+    // with nothing to check it against, the archive is refused even with no launcher installed
+    let (status, output) = harness.run_with_env(&["install"], &env);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(output.contains("launcher.allow_unverified"), "{}", output);
+    assert!(!launcher.exists());
+
+    let allowed = [
+        env[0],
+        env[1],
+        ("BRIXIDE_LAUNCHER_ALLOW_UNVERIFIED", "true"),
+    ];
+    let (status, output) = harness.run_with_env(&["install"], &allowed);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("installing it unverified"), "{}", output);
+    assert_eq!(fs::read_to_string(&launcher).unwrap(), "unverified");
+}
+
+#[test]
+fn refuses_the_default_launcher_without_a_known_digest() {
+    let harness = Harness::new("refuses_the_default_launcher_without_a_known_digest");
+    let (archive, _) = launcher_archive(&harness.dir, "default");
+    let (url, _, _) = serve(archive, "\"default\"");
+    let launcher = harness
+        .dir
+        .join("data/brickadia-launcher/main-brickadia-launcher");
+
+    // leaving the version unset is no way around the check
+    let (status, output) = harness.run_with_env(&["install"], &[("BRIXIDE_LAUNCHER_URL", &url)]);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(output.contains("no known SHA-256"), "{}", output);
+    assert!(!launcher.exists());
+}
+
+#[test]
+fn resumes_an_interrupted_download() {
+    let harness = Harness::new("resumes_an_interrupted_download");
+    let (archive, sha256) = launcher_archive(&harness.dir, "resumed");
+    let (url, served, requests) = serve(archive, "\"resumed\"");
+    served.lock().unwrap().cut_next = true;
+
+    // the archive is unpacked without tar or xz on the PATH
+    let env = [
+        ("PATH", ""),
+        ("BRIXIDE_LAUNCHER_URL", url.as_str()),
+        ("BRIXIDE_LAUNCHER_SHA256", sha256.as_str()),
+    ];
+    let (status, output) = harness.run_with_env(&["install"], &env);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    let partial = partial_downloads(&harness);
+    assert_eq!(partial.len(), 1, "{:?}", partial);
+    // named after the version it's part of
+    assert!(partial[0].starts_with("launcher-1.4-"), "{:?}", partial);
+    let downloaded = fs::metadata(harness.dir.join("data").join(&partial[0]))
+        .unwrap()
+        .len();
+    assert!(downloaded > 0);
+
+    let (status, output) = harness.run_with_env(&["install"], &env);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("Resuming the download"), "{}", output);
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![
+            (None, None),
+            (Some(downloaded), Some("\"resumed\"".to_owned()))
+        ]
+    );
+    assert_eq!(
+        fs::read_to_string(
+            harness
+                .dir
+                .join("data/brickadia-launcher/main-brickadia-launcher")
+        )
+        .unwrap(),
+        "resumed"
+    );
+    assert!(partial_downloads(&harness).is_empty());
+    assert!(!harness.dir.join("data/.brickadia-launcher.tmp").exists());
+    assert!(!harness.dir.join("data/brickadia-launcher.old").exists());
+}
+
+#[test]
+fn starts_over_when_the_archive_changed() {
+    let harness = Harness::new("starts_over_when_the_archive_changed");
+    let (old_archive, _) = launcher_archive(&harness.dir, "old");
+    let (url, served, requests) = serve(old_archive, "\"old\"");
+    served.lock().unwrap().cut_next = true;
+    let env = [("BRIXIDE_LAUNCHER_URL", url.as_str())];
+    let (status, output) = harness.run_with_env(&["install"], &env);
+    assert_eq!(status.code(), Some(1), "{}", output);
+
+    // the server sends the new archive whole, as it doesn't match the old one's ETag
+    fs::remove_dir_all(harness.dir.join("archive")).unwrap();
+    let (new_archive, sha256) = launcher_archive(&harness.dir, "new");
+    {
+        let mut served = served.lock().unwrap();
+        served.body = new_archive;
+        served.etag = "\"new\"".into();
+    }
+    let env = [
+        ("BRIXIDE_LAUNCHER_URL", url.as_str()),
+        ("BRIXIDE_LAUNCHER_SHA256", sha256.as_str()),
+    ];
+    let (status, output) = harness.run_with_env(&["install"], &env);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("archive changed"), "{}", output);
+    let asked = requests.lock().unwrap().clone();
+    assert_eq!(asked[1].1.as_deref(), Some("\"old\""), "{:?}", asked);
+    assert_eq!(
+        fs::read_to_string(
+            harness
+                .dir
+                .join("data/brickadia-launcher/main-brickadia-launcher")
+        )
+        .unwrap(),
+        "new"
+    );
+
+    // a partial download from elsewhere isn't resumed, and is cleaned up
+    let (other_url, other, other_requests) = serve(vec![0; 1024], "\"other\"");
+    other.lock().unwrap().cut_next = true;
+    let env = [("BRIXIDE_LAUNCHER_URL", other_url.as_str())];
+    let (status, output) = harness.run_with_env(&["install"], &env);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert_eq!(partial_downloads(&harness).len(), 1);
+    let env = [
+        ("BRIXIDE_LAUNCHER_URL", url.as_str()),
+        ("BRIXIDE_LAUNCHER_SHA256", sha256.as_str()),
+    ];
+    let (status, output) = harness.run_with_env(&["install"], &env);
+    assert!(status.success(), "{}", output);
+    assert_eq!(requests.lock().unwrap().last(), Some(&(None, None)));
+    assert_eq!(other_requests.lock().unwrap().len(), 1);
+    assert!(partial_downloads(&harness).is_empty());
+}
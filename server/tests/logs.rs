@@ -0,0 +1,82 @@
+mod common;
+
+use std::fs;
+
+use common::Harness;
+
+#[test]
+fn rotates_plugin_logs() {
+    let mut harness = Harness::new("rotates_plugin_logs");
+    // about 5 MB of stderr, which fills the log five times over
+    let target = harness.shell_script(
+        "chatty.sh",
+        &format!(
+            "yes {} | head -n 40000 >&2\n\
+             echo last line >&2\n\
+             while read line; do\n\
+             case \"$line\" in *shutdown*) exit 0;; esac\n\
+             done\n",
+            "x".repeat(100)
+        ),
+    );
+    harness.plugin("chatty", &target);
+    harness.start();
+
+    let logs = harness.dir.join("data/logs/plugins");
+    let log = harness.wait_for_file(&logs.join("chatty.log"), |log| log.contains("last line"));
+    assert!(log.len() <= 1024 * 1024);
+
+    for n in 1..=3 {
+        let rotated = fs::metadata(logs.join(format!("chatty.log.{}", n))).unwrap();
+        assert!(
+            rotated.len() <= 1024 * 1024,
+            "chatty.log.{} is too large",
+            n
+        );
+        assert!(
+            rotated.len() > 1000 * 1000,
+            "chatty.log.{} wasn't filled",
+            n
+        );
+    }
+    assert!(!logs.join("chatty.log.4").exists());
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn logs_plugin_stderr_at_its_level() {
+    let mut harness = Harness::new("logs_plugin_stderr_at_its_level");
+    let target = harness.shell_script(
+        "noisy.sh",
+        "echo just talking >&2\n\
+         echo 'WARNING: running low' >&2\n\
+         echo '[2024-01-01T00:00:00Z ERROR noisy] it broke' >&2\n\
+         echo '[debug] details' >&2\n\
+         echo last line >&2\n\
+         while read line; do\n\
+         case \"$line\" in *shutdown*) exit 0;; esac\n\
+         done\n",
+    );
+    harness.plugin("noisy", &target);
+    harness.start();
+
+    let log = harness.wait_for_file(&harness.dir.join("data/logs/plugins/noisy.log"), |log| {
+        log.contains("last line")
+    });
+    let level = |text: &str| {
+        let line = log
+            .lines()
+            .find(|line| line.contains(text))
+            .unwrap_or_else(|| panic!("no {} in {}", text, log));
+        line.split_whitespace().nth(1).unwrap().to_owned()
+    };
+    assert_eq!(level("just talking"), "INFO");
+    assert_eq!(level("running low"), "WARN");
+    assert_eq!(level("it broke"), "ERROR");
+    assert_eq!(level("details"), "DEBUG");
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
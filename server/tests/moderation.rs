@@ -0,0 +1,132 @@
+mod common;
+
+use std::fs;
+
+use common::Harness;
+
+#[test]
+fn kicks_banned_players_on_connect() {
+    let mut harness = Harness::new("kicks_banned_players_on_connect");
+    fs::write(
+        harness.dir.join("data/bans.json"),
+        r#"[{
+            "uuid": "0c3f7a56-8f4e-4d0b-9a43-6a1d2b3c4d5e",
+            "name": "Eve",
+            "reason": "griefing",
+            "issuer": "test",
+            "created": 0,
+            "expires": null
+        }, {
+            "uuid": "7e57f1a2-0000-4000-8000-000000000000",
+            "name": "Mallory",
+            "reason": "expired",
+            "issuer": "test",
+            "created": 0,
+            "expires": 1
+        }]"#,
+    )
+    .unwrap();
+    harness.script(
+        "wait 500\n\
+         join Mallory 7e57f1a2-0000-4000-8000-000000000000\n\
+         join Eve 0c3f7a56-8f4e-4d0b-9a43-6a1d2b3c4d5e\n",
+    );
+    harness.start();
+
+    harness.expect_recorded(|line| line == "Chat.Command /Kick \"Eve\" \"griefing\"");
+    assert!(!harness
+        .recorded()
+        .iter()
+        .any(|line| line.contains("Mallory")));
+}
+
+const ALICE: &str = "2b8c1d4e-1111-4000-8000-000000000001";
+const BOB: &str = "2b8c1d4e-2222-4000-8000-000000000002";
+const CAROL: &str = "2b8c1d4e-3333-4000-8000-000000000003";
+
+#[test]
+fn moderates_players_over_rpc() {
+    let mut harness = Harness::new("moderates_players_over_rpc");
+    // an expired ban, which is dropped the next time the list is saved
+    fs::write(
+        harness.dir.join("data/bans.json"),
+        r#"[{
+            "uuid": "7e57f1a2-0000-4000-8000-000000000000",
+            "name": "Mallory",
+            "reason": "expired",
+            "issuer": "test",
+            "created": 0,
+            "expires": 1
+        }]"#,
+    )
+    .unwrap();
+    harness.script(&format!(
+        "wait 500\njoin Alice {}\njoin Bob {}\nwait 60000\n",
+        ALICE, BOB
+    ));
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"players.kick","params":{"target":"Alice","reason":"spamming"}}"#.to_owned(),
+        r#"{"jsonrpc":"2.0","id":2,"method":"players.ban","params":{"target":"Bob","reason":"griefing"}}"#.to_owned(),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"players.ban","params":{{"target":"{}","reason":"alt","duration":3600}}}}"#,
+            CAROL
+        ),
+        r#"{"jsonrpc":"2.0","id":4,"method":"players.ban","params":{"target":"Nobody"}}"#.to_owned(),
+        // Carol was banned by UUID alone, so her ban has no name to match an empty target
+        r#"{"jsonrpc":"2.0","id":7,"method":"players.unban","params":{"target":""}}"#.to_owned(),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":5,"method":"players.unban","params":{{"target":"{}"}}}}"#,
+            CAROL
+        ),
+        r#"{"jsonrpc":"2.0","id":6,"method":"players.unban","params":{"target":"Nobody"}}"#.to_owned(),
+    ];
+    // the requests are sent once both players have connected
+    let received = harness.received("moderator");
+    harness.recording_plugin(
+        "moderator",
+        &format!(
+            "(until grep -qs '\"name\":\"Bob\"' {}; do sleep 0.05; done\n\
+             {}) &\n",
+            received.display(),
+            requests
+                .iter()
+                .map(|request| format!("echo '{}'\n", request))
+                .collect::<String>()
+        ),
+    );
+    harness.start();
+
+    let replies = harness.wait_for_file(&received, |replies| replies.contains(r#""id":6"#));
+    let reply = |id: i32| {
+        replies
+            .lines()
+            .find(|line| line.contains(&format!("\"id\":{}", id)))
+            .unwrap_or_else(|| panic!("no reply to {} in {}", id, replies))
+    };
+    for id in [1, 2, 3, 5] {
+        assert!(reply(id).contains(r#""result":null"#), "{}", reply(id));
+    }
+    assert!(reply(4).contains("-32000"), "{}", reply(4));
+    assert!(reply(6).contains("-32001"), "{}", reply(6));
+    assert!(reply(7).contains("-32602"), "{}", reply(7));
+
+    // kicks and bans of online players go to the game
+    harness.expect_recorded(|line| line == "Chat.Command /Kick \"Alice\" \"spamming\"");
+    harness.expect_recorded(|line| line == "Chat.Command /Kick \"Bob\" \"griefing\"");
+
+    // only Bob's ban is left on disk: Carol's was lifted and Mallory's had expired
+    let bans: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(harness.dir.join("data/bans.json")).unwrap())
+            .unwrap();
+    let bans = bans.as_array().unwrap();
+    assert_eq!(bans.len(), 1, "{:?}", bans);
+    assert_eq!(bans[0]["uuid"], BOB);
+    assert_eq!(bans[0]["name"], "Bob");
+    assert_eq!(bans[0]["reason"], "griefing");
+    assert_eq!(bans[0]["issuer"], "moderator");
+    assert!(bans[0]["expires"].is_null());
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{capabilities, Harness};
+
+#[test]
+fn refuses_calls_until_approved() {
+    let mut harness = Harness::new("refuses_calls_until_approved");
+    let target = harness.recording_script(
+        "announcer",
+        "echo '{\"jsonrpc\":\"2.0\",\"method\":\"writeln\",\"params\":\"Server.Status\"}'\n\
+         echo '{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"whisper\",\"params\":{\"target\":\"Alice\",\"content\":\"hi\"}}'\n\
+         echo '{\"jsonrpc\":\"2.0\",\"method\":\"broadcast\",\"params\":\"Hello everyone\"}'\n",
+    );
+    harness.plugin_with("announcer", &target, &capabilities(&["broadcast"]));
+    harness.start();
+
+    // console isn't declared, and broadcast isn't approved yet
+    let replies = harness.wait_for_file(&harness.received("announcer"), |replies| {
+        replies.contains("\"id\":1")
+    });
+    assert!(replies.contains("-32002"), "{}", replies);
+    harness.expect_output(
+        "Plugin announcer was denied writeln: writeln needs the console capability, which the plugin doesn't declare",
+    );
+    harness.expect_output(
+        "Plugin announcer was denied broadcast: broadcast needs the broadcast capability, which the operator hasn't approved",
+    );
+
+    harness.console(".approve announcer");
+    harness.expect_output("Approved capabilities for plugin announcer: broadcast");
+    harness.expect_recorded(|line| line == "Chat.Broadcast Hello everyone");
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+    assert!(!harness
+        .recorded()
+        .iter()
+        .any(|line| line == "Server.Status"));
+}
+
+#[test]
+fn passes_messages_between_plugins() {
+    let mut harness = Harness::new("passes_messages_between_plugins");
+    let received = harness.recording_plugin("receiver", "");
+    let sender = harness.recording_script(
+        "sender",
+        "echo '{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"plugins.send\",\"params\":{\"target\":\"receiver\",\"message\":{\"hello\":true}}}'\n",
+    );
+    // the receiver may start after the sender's message, so it's sent again on reload
+    harness.plugin_with("sender", &sender, &capabilities(&["messaging"]));
+    harness.approve("sender", &["messaging"]);
+    harness.start();
+    harness.expect_output("Started 2 plugins");
+    harness.console(".reload sender");
+
+    let messages =
+        harness.wait_for_file(&received, |messages| messages.contains("plugins.message"));
+    assert!(
+        messages.contains(
+            r#""method":"plugins.message","params":{"from":"sender","message":{"hello":true}}"#
+        ),
+        "{}",
+        messages
+    );
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
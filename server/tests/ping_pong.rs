@@ -0,0 +1,67 @@
+mod common;
+
+use common::{ping_pong_plugin, Harness};
+
+#[test]
+fn broadcasts_on_connect() {
+    let mut harness = Harness::new("broadcasts_on_connect");
+    harness.plugin("ping_pong_plugin", &ping_pong_plugin());
+    harness.script(
+        "wait 500\n\
+         join Alice 5b0e1b1e-1d5c-4e4a-a4f5-ef6a7a1b2c3d\n",
+    );
+    harness.start();
+
+    harness.expect_recorded(|line| {
+        line == "Chat.Broadcast <b>Alice</> is connecting! Their UUID is 5b0e1b1e-1d5c-4e4a-a4f5-ef6a7a1b2c3d"
+    });
+}
+
+#[test]
+fn writes_lines_from_chat() {
+    let mut harness = Harness::new("writes_lines_from_chat");
+    harness.plugin("ping_pong_plugin", &ping_pong_plugin());
+    harness.script(
+        "wait 500\n\
+         join Bob\n\
+         chat Bob writeln:Server.Status\n\
+         leave Bob\n",
+    );
+    harness.start();
+
+    harness.expect_recorded(|line| line == "Server.Status");
+}
+
+#[test]
+fn whispers_replies() {
+    let mut harness = Harness::new("whispers_replies");
+    harness.plugin("ping_pong_plugin", &ping_pong_plugin());
+    harness.script(
+        "wait 500\n\
+         join Carol\n\
+         chat Carol ping\n",
+    );
+    harness.start();
+
+    harness.expect_recorded(|line| line == "Chat.Whisper \"Carol\" pong");
+}
+
+#[test]
+fn does_not_whisper_offline_players() {
+    let mut harness = Harness::new("does_not_whisper_offline_players");
+    harness.plugin("ping_pong_plugin", &ping_pong_plugin());
+    harness.script(
+        "wait 500\n\
+         join Dave\n\
+         leave Dave\n\
+         chat Dave ping\n\
+         chat Dave writeln:Server.Status\n",
+    );
+    harness.start();
+
+    harness.expect_recorded(|line| line == "Server.Status");
+    assert!(!harness
+        .recorded()
+        .iter()
+        .any(|line| line.starts_with("Chat.Whisper")));
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::Harness;
+
+#[test]
+fn reloads_changed_plugins() {
+    let mut harness = Harness::new("reloads_changed_plugins");
+    let received = harness.recording_plugin("listener", "");
+    harness.recording_plugin("watched", "");
+    let watched = harness.recording_script("watched", "");
+    harness.start_with(&["--watch-plugins"]);
+    harness.expect_output("Started 2 plugins");
+
+    harness.plugin_with("watched", &watched, "# changed\n");
+    harness.expect_output("Reloading plugin watched (its files changed)");
+
+    // the other plugin, which starts first, hears about the reload
+    let messages = harness.wait_for_file(&received, |messages| {
+        messages.matches("plugin.started").count() >= 2
+    });
+    let methods: Vec<&str> = messages
+        .lines()
+        .filter(|line| line.contains("\"id\":\"watched\""))
+        .map(|line| {
+            if line.contains("plugin.stopped") {
+                "stopped"
+            } else {
+                "started"
+            }
+        })
+        .collect();
+    assert_eq!(methods, ["started", "stopped", "started"], "{}", messages);
+
+    harness.console(".reload");
+    harness.expect_output("Reloaded 2 plugins");
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn reloads_on_request_without_blocking() {
+    let mut harness = Harness::new("reloads_on_request_without_blocking");
+    fs::write(
+        harness.dir.join("brixide.toml"),
+        "[plugins]\ngrace_period = 2\n",
+    )
+    .unwrap();
+    // ignores being told to shut down, so stopping it takes the whole grace period
+    let stubborn = harness.shell_script("stubborn.sh", "while read line; do :; done\n");
+    harness.plugin("stubborn", &stubborn);
+    let received = harness.recording_plugin(
+        "reloader",
+        "echo '{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"plugins.reload\",\"params\":{\"id\":\"stubborn\"}}'\n",
+    );
+    harness.start();
+
+    let replies = harness.wait_for_file(&received, |replies| replies.contains("\"id\":1"));
+    assert!(replies.contains(r#""result":null"#), "{}", replies);
+    harness.expect_output("Reloading plugin stubborn (requested by reloader)");
+
+    // the console keeps working while the plugin is being stopped
+    harness.console(".players");
+    harness.expect_output("0 players online");
+    harness.expect_output("Reloaded plugin stubborn");
+    let output = harness.output();
+    let answered = output.find("0 players online").unwrap();
+    assert!(
+        answered < output.find("Plugin stubborn didn't exit in time").unwrap(),
+        "{}",
+        output
+    );
+    assert!(answered < output.find("Reloaded plugin stubborn").unwrap());
+
+    harness.signal("TERM");
+    harness.wait_for_exit();
+}
+
+#[test]
+fn reloads_interpreted_plugins_when_their_files_change() {
+    let mut harness = Harness::new("reloads_interpreted_plugins_when_their_files_change");
+    let wait = "while read line; do case \"$line\" in *shutdown*) exit 0;; esac; done";
+    // the script named in its arguments is watched
+    harness.plugin_with(
+        "scripted",
+        Path::new("unused"),
+        "command = \"sh\"\nargs = [\"main.sh\"]\n",
+    );
+    let script = harness.dir.join("plugins/scripted/main.sh");
+    fs::write(&script, format!("{}\n", wait)).unwrap();
+    // without any files in its arguments, its whole folder is
+    harness.plugin_with(
+        "inline",
+        Path::new("unused"),
+        &format!("command = \"sh\"\nargs = [\"-c\", {:?}]\n", wait),
+    );
+    harness.start_with(&["--watch-plugins"]);
+    harness.expect_output("Started 2 plugins");
+
+    fs::write(&script, format!("# changed\n{}\n", wait)).unwrap();
+    harness.expect_output("Reloading plugin scripted (its files changed)");
+    harness.expect_output("Reloaded plugin scripted");
+
+    fs::write(harness.dir.join("plugins/inline/lib.sh"), "# new\n").unwrap();
+    harness.expect_output("Reloading plugin inline (its files changed)");
+    harness.expect_output("Reloaded plugin inline");
+
+    // a file it already knows about is checked without walking the folder again
+    fs::write(harness.dir.join("plugins/inline/lib.sh"), "# changed\n").unwrap();
+    harness.wait_for_file(&harness.dir.join("wrapper.log"), |output| {
+        output.matches("Reloaded plugin inline").count() == 2
+    });
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn starts_plugins_added_while_running() {
+    let mut harness = Harness::new("starts_plugins_added_while_running");
+    harness.start_with(&["--watch-plugins"]);
+    harness.expect_output("Started 0 plugins");
+
+    let received = harness.recording_plugin("added", "");
+    harness.expect_output("Reloading plugin added (it was added)");
+    harness.expect_output("Reloaded plugin added");
+    harness.wait_for_file(&received, |messages| messages.contains("\"config\""));
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn keeps_events_for_plugins_being_reloaded() {
+    let mut harness = Harness::new("keeps_events_for_plugins_being_reloaded");
+    fs::write(
+        harness.dir.join("brixide.toml"),
+        "[plugins]\ngrace_period = 10\n",
+    )
+    .unwrap();
+    // takes a while to stop, so the player joins between its old instance and its new one
+    let received = harness.received("slow");
+    let slow = harness.shell_script(
+        "slow.sh",
+        &format!(
+            "while read line; do\n\
+             echo \"$line\" >> {}\n\
+             case \"$line\" in *shutdown*) sleep 4; exit 0;; esac\n\
+             done\n",
+            received.display()
+        ),
+    );
+    harness.plugin("slow", &slow);
+    harness.script("wait 1500\njoin Alice\n");
+    harness.start();
+    harness.expect_output("Started 1 plugins");
+
+    harness.console(".reload slow");
+    harness.expect_output("Reloaded plugin slow");
+
+    // the new instance gets the join after its config
+    let messages = harness.wait_for_file(&received, |messages| messages.contains("\"connect\""));
+    let restarted = messages.rfind("\"config\"").unwrap();
+    assert!(
+        messages.find("\"connect\"").unwrap() > restarted,
+        "{}",
+        messages
+    );
+
+    harness.signal("TERM");
+    harness.wait_for_exit();
+}
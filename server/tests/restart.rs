@@ -0,0 +1,76 @@
+mod common;
+
+use std::fs;
+
+use common::{ping_pong_plugin, Harness};
+
+#[test]
+fn restarts_the_game_until_it_crashes_too_often() {
+    let mut harness = Harness::new("restarts_the_game_until_it_crashes_too_often");
+    harness.plugin("ping_pong_plugin", &ping_pong_plugin());
+    let received = harness.recording_plugin("listener", "");
+    harness.script(
+        "wait 500\n\
+         join Alice\n\
+         wait 500\n\
+         crash 3\n",
+    );
+    harness.start_with(&["--max-crashes", "3"]);
+
+    // a line sent while the game is down isn't replayed to the next one
+    harness.expect_output("Restarting the game in");
+    harness.console("Stale.Command");
+
+    let status = harness.wait_for_exit();
+    assert_eq!(status.code(), Some(2), "{}", harness.output());
+
+    // the plugin kept running across restarts, announcing each join
+    let joins = harness
+        .recorded()
+        .iter()
+        .filter(|line| line.contains("is connecting"))
+        .count();
+    assert_eq!(joins, 3);
+    assert_eq!(harness.output().matches("Server restarted").count(), 2);
+    assert!(!harness
+        .recorded()
+        .iter()
+        .any(|line| line == "Stale.Command"));
+    harness.expect_output("Dropped 1 lines sent to the game while it was down");
+
+    // and plugins heard about each restart
+    let messages = fs::read_to_string(received).unwrap();
+    assert_eq!(
+        messages.matches("server.restarted").count(),
+        2,
+        "{}",
+        messages
+    );
+}
+
+#[test]
+fn restarts_plugins_that_fail() {
+    let mut harness = Harness::new("restarts_plugins_that_fail");
+    let crasher = harness.shell_script("crasher.sh", "echo boom >&2\nsleep 0.5\nexit 3\n");
+    harness.plugin_with("crasher", &crasher, "restart = \"on-failure\"\n");
+    let quitter = harness.shell_script("quitter.sh", "exit 0\n");
+    harness.plugin_with("quitter", &quitter, "restart = \"never\"\n");
+    harness.start();
+
+    harness.expect_output("Plugin crasher exited unexpectedly (exit status: 3)");
+    harness.expect_output("Restarting plugin crasher in 1s");
+    harness.expect_output("Restarted plugin crasher");
+    harness.expect_output("Restarting plugin crasher in 2s");
+
+    harness.signal("TERM");
+    harness.wait_for_exit();
+    assert!(harness
+        .output()
+        .contains("Plugin quitter exited (exit status: 0)"));
+    assert!(!harness.output().contains("Restarting plugin quitter"));
+
+    // stderr is logged, and kept in the plugin's own log
+    assert!(harness.output().contains("[crasher] boom"));
+    let log = fs::read_to_string(harness.dir.join("data/logs/plugins/crasher.log")).unwrap();
+    assert!(log.matches("INFO  boom").count() >= 2, "{}", log);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::{fs, path::Path, process::Command};
+
+use common::Harness;
+
+#[test]
+fn creates_script_plugins() {
+    let mut harness = Harness::new("creates_script_plugins");
+    let (status, output) = harness.run(&["plugin", "new", "greeter", "--template", "script"]);
+    assert!(status.success(), "{}", output);
+
+    let (status, output) = harness.run(&["plugin", "check", "plugins/greeter"]);
+    assert!(status.success(), "{}", output);
+
+    harness.approve("greeter", &["broadcast"]);
+    harness.script(
+        "wait 500\n\
+         join Alice\n",
+    );
+    harness.start();
+    harness.expect_recorded(|line| line == "Chat.Broadcast Welcome, <b>Alice</>!");
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
+
+#[test]
+fn creates_rust_plugins_that_build() {
+    let harness = Harness::new("creates_rust_plugins_that_build");
+    let (status, output) = harness.run(&["plugin", "new", "hello-rust"]);
+    assert!(status.success(), "{}", output);
+
+    let dir = harness.dir.join("plugins/hello-rust");
+    let manifest = fs::read_to_string(dir.join("plugin.toml")).unwrap();
+    assert!(
+        manifest.contains("target = \"target/debug/hello-rust\""),
+        "{}",
+        manifest
+    );
+
+    // share a target folder with the other plugins built by the tests, so dependencies are only built once
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ping_pong_plugin");
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(dir.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .expect("Failed to run cargo");
+    assert!(status.success(), "the generated plugin didn't build");
+
+    fs::create_dir_all(dir.join("target/debug")).unwrap();
+    fs::copy(
+        target_dir.join("debug/hello-rust"),
+        dir.join("target/debug/hello-rust"),
+    )
+    .unwrap();
+    let (status, output) = harness.run(&["plugin", "check", "plugins/hello-rust"]);
+    assert!(status.success(), "{}", output);
+
+    let (status, output) = harness.run(&["plugin", "new", "hello-rust"]);
+    assert!(!status.success());
+    assert!(output.contains("already exists"), "{}", output);
+}
+
+#[test]
+fn points_rust_plugins_at_the_sdk_from_any_plugins_folder() {
+    let harness = Harness::new("points_rust_plugins_at_the_sdk_from_any_plugins_folder");
+    // as if the wrapper ran from a checkout of the repository
+    let sdk = Path::new(env!("CARGO_MANIFEST_DIR")).join("../plugin");
+    std::os::unix::fs::symlink(&sdk, harness.dir.join("plugin")).unwrap();
+
+    let (status, output) = harness.run(&[
+        "--plugins-dir",
+        "servers/pvp/plugins",
+        "plugin",
+        "new",
+        "nested",
+    ]);
+    assert!(status.success(), "{}", output);
+
+    let dir = harness.dir.join("servers/pvp/plugins/nested");
+    let cargo_toml = fs::read_to_string(dir.join("Cargo.toml")).unwrap();
+    let path = cargo_toml
+        .lines()
+        .find_map(|line| line.strip_prefix("plugin = { path = \""))
+        .and_then(|rest| rest.strip_suffix("\" }"))
+        .unwrap_or_else(|| panic!("no path to the plugin crate in {}", cargo_toml));
+    assert!(Path::new(path).is_relative(), "{}", path);
+    assert!(dir.join(path).join("Cargo.toml").is_file(), "{}", path);
+}
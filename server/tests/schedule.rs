@@ -0,0 +1,67 @@
+mod common;
+
+use std::{fs, thread::sleep, time::Duration};
+
+use common::Harness;
+
+const REQUESTS: &[&str] = &[
+    r#"{"jsonrpc":"2.0","id":1,"method":"schedule.create","params":{"name":"tick","every":1}}"#,
+    r#"{"jsonrpc":"2.0","id":2,"method":"schedule.create","params":{"name":"soon","delay":0.2,"persist":true}}"#,
+    r#"{"jsonrpc":"2.0","id":3,"method":"schedule.create","params":{"name":"nightly","cron":"0 3 * * *","persist":true}}"#,
+    r#"{"jsonrpc":"2.0","id":4,"method":"schedule.create","params":{"name":"bad","cron":"every night"}}"#,
+];
+
+#[test]
+fn fires_schedules() {
+    let mut harness = Harness::new("fires_schedules");
+    let received = harness.received("scheduler");
+    let requests: String = REQUESTS
+        .iter()
+        .map(|request| format!("echo '{}'\n", request))
+        .collect();
+    // the plugin cancels its tick schedule the first time it fires
+    let cancel = format!(
+        "(until grep -qs '\"name\":\"tick\"' {}; do sleep 0.05; done; echo '{}') &\n",
+        received.display(),
+        r#"{"jsonrpc":"2.0","id":5,"method":"schedule.cancel","params":{"name":"tick"}}"#
+    );
+    harness.recording_plugin("scheduler", &format!("{}{}", requests, cancel));
+    harness.start();
+
+    let lines = harness.wait_for_file(&received, |lines| lines.contains(r#""id":5"#));
+    let line = |text: &str| {
+        lines
+            .lines()
+            .find(|line| line.contains(text))
+            .unwrap_or_else(|| panic!("no {} in {}", text, lines))
+            .to_owned()
+    };
+    assert!(line(r#""id":4"#).contains("-32602"), "{}", lines);
+    assert!(line(r#""id":5"#).contains(r#""result":true"#), "{}", lines);
+    line(r#""method":"schedule.fired","params":{"name":"soon"}"#);
+
+    // the cancelled tick doesn't fire again
+    sleep(Duration::from_millis(1500));
+    let lines = fs::read_to_string(&received).unwrap();
+    assert_eq!(lines.matches(r#"{"name":"tick"}"#).count(), 1, "{}", lines);
+
+    // only the persisted schedule that hasn't fired yet is kept
+    let persisted =
+        fs::read_to_string(harness.dir.join("data/plugins/scheduler/schedules.json")).unwrap();
+    assert!(persisted.contains("nightly"), "{}", persisted);
+    assert!(!persisted.contains("soon"), "{}", persisted);
+    assert!(!persisted.contains("tick"), "{}", persisted);
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+
+    // and it's back after a restart, without the plugin creating it again
+    harness.recording_script("scheduler", "");
+    harness.start();
+    harness.expect_output("Started 1 plugins");
+    harness.console(".schedules scheduler");
+    harness.expect_output("nightly - cron 0 3 * * *, persisted, next at");
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use std::fs;
+
+use common::Harness;
+
+#[test]
+fn merges_settings_from_file_environment_and_flags() {
+    let harness = Harness::new("merges_settings_from_file_environment_and_flags");
+    fs::write(
+        harness.dir.join("brixide.toml"),
+        "port = 8000\n\
+         plugins_dir = \"addons\"\n\
+         colour = true\n\
+         [game]\n\
+         args = [\"-Example\"]\n\
+         [plugins]\n\
+         restart = \"never\"\n\
+         [instances.main]\n\
+         server.nmae = \"Typo\"\n",
+    )
+    .unwrap();
+
+    let (status, output) = harness.run(&["config", "show"]);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("port = 8000"), "{}", output);
+    assert!(output.contains("plugins_dir = \"addons\""), "{}", output);
+    assert!(output.contains("args = [\"-Example\"]"), "{}", output);
+    assert!(output.contains("restart = \"never\""), "{}", output);
+    assert!(output.contains("Unknown key colour"), "{}", output);
+    assert!(
+        output.contains("Unknown key instances.main.server.nmae"),
+        "{}",
+        output
+    );
+
+    // the environment overrides the file, and flags override both
+    let env = [("BRIXIDE_PORT", "9000"), ("BRIXIDE_GAME_ARGS", "-One -Two")];
+    let (status, output) = harness.run_with_env(&["config", "show"], &env);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("port = 9000"), "{}", output);
+    assert!(output.contains("args = [\"-One\", \"-Two\"]"), "{}", output);
+    let (status, output) = harness.run_with_env(&["--port", "9100", "config", "show"], &env);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("port = 9100"), "{}", output);
+
+    // the plugins folder is read from wherever the config says
+    let target = harness.shell_script("addon.sh", "exit 0\n");
+    harness.plugin_with("unused", &target, "");
+    fs::create_dir_all(harness.dir.join("addons")).unwrap();
+    fs::rename(
+        harness.dir.join("plugins/unused"),
+        harness.dir.join("addons/addon"),
+    )
+    .unwrap();
+    let (status, output) = harness.run(&["plugin", "check"]);
+    assert!(status.success(), "{}", output);
+    assert!(output.contains("addons/addon is ok"), "{}", output);
+
+    let (status, output) = harness.run_with_env(&["config", "show"], &[("BRIXIDE_PORT", "lots")]);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(
+        output.contains("BRIXIDE_PORT should be a number"),
+        "{}",
+        output
+    );
+
+    fs::write(harness.dir.join("brixide.toml"), "port = \"high\"\n").unwrap();
+    let (status, output) = harness.run(&["config", "show"]);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(output.contains("line 1, column 8"), "{}", output);
+
+    // an empty game command is a config error rather than a crash
+    fs::remove_file(harness.dir.join("brixide.toml")).unwrap();
+    let (status, output) = harness.run(&["--game-command", "", "config", "show"]);
+    assert_eq!(status.code(), Some(1), "{}", output);
+    assert!(output.contains("game.command is empty"), "{}", output);
+}
+
+#[test]
+fn hides_server_passwords() {
+    let harness = Harness::new("hides_server_passwords");
+    fs::write(
+        harness.dir.join("brixide.toml"),
+        "[server]
+         password = \"hunter2\"
+         [instances.pvp.server]
+         password = \"swordfish\"
+",
+    )
+    .unwrap();
+
+    let (status, output) = harness.run(&["config", "show"]);
+    assert!(status.success(), "{}", output);
+    assert!(!output.contains("hunter2"), "{}", output);
+    assert!(!output.contains("swordfish"), "{}", output);
+    assert!(output.contains("password = \"<hidden>\""), "{}", output);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::fs;
+
+use common::{ping_pong_plugin, Harness};
+
+#[test]
+fn stops_gracefully_on_sigterm() {
+    let mut harness = Harness::new("stops_gracefully_on_sigterm");
+    harness.plugin("ping_pong_plugin", &ping_pong_plugin());
+    harness.start();
+    harness.expect_output("Test log from console");
+
+    harness.signal("TERM");
+    let status = harness.wait_for_exit();
+
+    assert_eq!(status.code(), Some(0), "{}", harness.output());
+    assert!(harness
+        .output()
+        .contains("[ping_pong_plugin] Shutting down"));
+    let recorded = harness.recorded();
+    assert!(recorded.iter().any(|line| line.starts_with("Bricks.Save")));
+    assert_eq!(recorded.last().map(String::as_str), Some("exit"));
+}
+
+#[test]
+fn stops_plugins_in_reverse_load_order() {
+    let mut harness = Harness::new("stops_plugins_in_reverse_load_order");
+    let exits = harness.dir.join("exits.txt");
+    // plugins that start later take longer to exit, so they'd finish last if they were all told
+    // to stop at once
+    let plugins = [
+        ("base", "", "0"),
+        ("middle", "depends = [\"base\"]\n", "0.3"),
+        ("top", "depends = [\"middle\"]\n", "0.6"),
+    ];
+    for (id, extra, delay) in plugins.iter() {
+        let target = harness.shell_script(
+            &format!("{}.sh", id),
+            &format!(
+                "while read line; do\n\
+                 case \"$line\" in *shutdown*) sleep {}; echo {} >> {}; exit 0;; esac\n\
+                 done\n",
+                delay,
+                id,
+                exits.display()
+            ),
+        );
+        harness.plugin_with(id, &target, extra);
+    }
+    harness.start();
+    harness.expect_output("Started 3 plugins");
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+    assert_eq!(
+        fs::read_to_string(exits).unwrap(),
+        "top\nmiddle\nbase\n",
+        "{}",
+        harness.output()
+    );
+}
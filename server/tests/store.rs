@@ -0,0 +1,68 @@
+mod common;
+
+use std::fs;
+
+use common::{capabilities, Harness};
+
+const REQUESTS: &[&str] = &[
+    r#"{"jsonrpc":"2.0","id":1,"method":"store.set","params":{"key":"pings.alice","value":3}}"#,
+    r#"{"jsonrpc":"2.0","id":2,"method":"store.set","params":{"key":"pings.bob","value":{"last":"pong"}}}"#,
+    r#"{"jsonrpc":"2.0","id":3,"method":"store.set","params":{"key":"motd","value":"hi"}}"#,
+    r#"{"jsonrpc":"2.0","id":4,"method":"store.list","params":{"prefix":"pings."}}"#,
+    r#"{"jsonrpc":"2.0","id":5,"method":"store.delete","params":{"key":"motd"}}"#,
+    r#"{"jsonrpc":"2.0","id":6,"method":"store.get","params":{"key":"pings.bob"}}"#,
+    r#"{"jsonrpc":"2.0","id":7,"method":"store.get","params":{"key":"motd"}}"#,
+    r#"{"jsonrpc":"2.0","id":8,"method":"store.set","params":{"key":"pings.alice","value":null}}"#,
+    r#"{"jsonrpc":"2.0","id":9,"method":"store.list","params":{"prefix":""}}"#,
+];
+
+#[test]
+fn stores_values_durably() {
+    let mut harness = Harness::new("stores_values_durably");
+    let requests: String = REQUESTS
+        .iter()
+        .map(|request| format!("echo '{}'\n", request))
+        .collect();
+    let target = harness.recording_script("storer", &requests);
+    harness.plugin_with("storer", &target, &capabilities(&["storage"]));
+    harness.approve("storer", &["storage"]);
+    harness.start();
+
+    let replies = harness.wait_for_file(&harness.received("storer"), |replies| {
+        replies.contains(r#""id":9"#)
+    });
+    let reply = |id: i32| {
+        replies
+            .lines()
+            .find(|line| line.contains(&format!("\"id\":{}", id)))
+            .unwrap_or_else(|| panic!("no reply to {} in {}", id, replies))
+    };
+    assert!(
+        reply(4).contains(r#""result":["pings.alice","pings.bob"]"#),
+        "{}",
+        reply(4)
+    );
+    assert!(reply(5).contains(r#""result":true"#), "{}", reply(5));
+    assert!(
+        reply(6).contains(r#""result":{"last":"pong"}"#),
+        "{}",
+        reply(6)
+    );
+    assert!(reply(7).contains(r#""result":null"#), "{}", reply(7));
+    // setting a key to null deletes it
+    assert!(reply(8).contains(r#""result":null"#), "{}", reply(8));
+    assert!(
+        reply(9).contains(r#""result":["pings.bob"]"#),
+        "{}",
+        reply(9)
+    );
+
+    let store: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(harness.dir.join("data/plugins/storer/store.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(store, serde_json::json!({"pings.bob": {"last": "pong"}}));
+
+    harness.signal("TERM");
+    assert!(harness.wait_for_exit().success(), "{}", harness.output());
+}